@@ -3,28 +3,48 @@
 //! This module contains the `Program` Type, `Statement` struct and
 //! `StmtKind` enum `Expression` struct and `ExprKind` enum,
 //! which together represent the various types of statements and expressions
-//! that can occur in Lox source code.
+//! that can occur in Lox source code, along with the `Visitor` trait for
+//! passes that traverse them.
+//!
+//! Derived `PartialEq` compares nodes including their positions; the
+//! `struct_eq` methods compare only what the program means.
 
-use crate::token::{Literal, Operator, TokenType};
+use crate::{
+    interner::Symbol,
+    token::{Literal, Operator, TokenType},
+};
 
 pub type Program = Vec<Declaration>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Declaration {
     pub kind: DeclKind,
     pub line: usize,
     pub column: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct VarDecl {
-    pub identifier: String,
+    pub identifier: Symbol,
     pub initializer: Option<Expression>,
+    /// Declared with `const`, so the variable cannot be assigned to afterwards.
+    pub constant: bool,
     pub line: usize,
     pub column: usize,
 }
 
-#[derive(Clone, Debug)]
+impl VarDecl {
+    /// Returns the keyword the variable was declared with.
+    pub fn keyword(&self) -> &'static str {
+        if self.constant {
+            "const"
+        } else {
+            "var"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Statement {
     pub kind: StmtKind,
     pub line: usize,
@@ -35,7 +55,7 @@ pub struct Statement {
 ///
 /// An expression is a combination of values, variables, operators,
 /// and function calls that can be evaluated to produce a value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Expression {
     /// The specific kind of expression.
     pub kind: ExprKind,
@@ -45,7 +65,16 @@ pub struct Expression {
     pub column: usize,
 }
 
-#[derive(Clone, Debug)]
+impl Expression {
+    /// Returns whether the expression may appear on the left of `=`.
+    ///
+    /// Variables and map entries can be assigned to.
+    pub fn is_assignable_target(&self) -> bool {
+        matches!(self.kind, ExprKind::Var { .. } | ExprKind::Index { .. })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum StmtKind {
     ExprStmt {
         expression: Box<Expression>,
@@ -58,60 +87,632 @@ pub enum StmtKind {
     WhileStmt {
         condition: Box<Expression>,
         do_stmt: Box<Statement>,
+        /// The name given by `label: while ...`, targeted by `break label;`.
+        label: Option<Symbol>,
     },
     ForStmt {
         initializer: Option<Box<Declaration>>,
         condition: Option<Box<Expression>>,
         update: Option<Box<Expression>>,
         body: Box<Statement>,
+        /// The name given by `label: for ...`, targeted by `break label;`.
+        label: Option<Symbol>,
     },
+    /// `print a, b, c;`, printing the values separated by spaces.
     PrintStmt {
-        expression: Box<Expression>,
+        expressions: Vec<Expression>,
+    },
+    /// Leaves the innermost enclosing loop or switch, or the loop with the
+    /// given label.
+    Break {
+        label: Option<Symbol>,
+    },
+    /// Skips to the next iteration of the innermost enclosing loop, or of the
+    /// loop with the given label.
+    Continue {
+        label: Option<Symbol>,
+    },
+    /// Runs the first case whose value equals the discriminant, or the
+    /// default case if none does. A case only falls through into the next
+    /// one, or into the default case after the last one, when its body ends
+    /// with `fallthrough;`. An unlabeled `break` leaves the switch.
+    Switch {
+        discriminant: Box<Expression>,
+        cases: Vec<SwitchCase>,
+        default: Option<Vec<Declaration>>,
     },
     Block {
         declarations: Vec<Declaration>,
     },
 }
 
-#[derive(Clone, Debug)]
+/// A single `case value: ...` arm of a switch statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwitchCase {
+    pub value: Expression,
+    pub body: Vec<Declaration>,
+    /// Whether the body ends with `fallthrough;`, running on into the next
+    /// case, or into the default case after the last one.
+    pub fallthrough: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum DeclKind {
     VarDecl(VarDecl),
+    /// Several variables declared by a single `var` statement, e.g. `var a = 1, b;`.
+    MultiVarDecl(Vec<VarDecl>),
     Statement(Statement),
 }
 
 /// Enumerates the different kinds of expressions in Lox.
-#[derive(Clone, Debug)]
+///
+/// `Unary`, `TypeOf` and `IncDec` are positioned at their operator token, so runtime
+/// type errors point at the operator. `Binary` and `Logical` start at their left
+/// operand and record the operator's position in `op_line` and `op_column` instead.
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExprKind {
     // Highest precedence
     Lit {
         value: Literal,
     },
     Var {
-        identifier: String,
+        identifier: Symbol,
     },
     Grouping {
         expression: Box<Expression>,
     },
+    /// A block in expression position, e.g. `{ var x = 1; x + 1 }`.
+    ///
+    /// Its value is the trailing expression without a `;`, or nil if there is none.
+    BlockExpr {
+        declarations: Vec<Declaration>,
+        tail: Option<Box<Expression>>,
+    },
+    /// A map literal such as `{"a": 1, "b": 2}`, with its entries in source order.
+    ///
+    /// In expression position, a `{` directly followed by `}`, or by a literal
+    /// and a `:`, starts a map literal; any other `{` starts a block expression.
+    MapLit {
+        entries: Vec<(Expression, Expression)>,
+    },
+    /// `object[index]`, reading the entry of a map.
+    Index {
+        object: Box<Expression>,
+        index: Box<Expression>,
+    },
     // High precedence
     Unary {
         operator: Operator,
         right: Box<Expression>,
     },
+    /// `typeof operand`, evaluating to the name of the operand's type.
+    TypeOf {
+        operand: Box<Expression>,
+    },
+    /// Prefix (`++i`) or postfix (`i++`) increment or decrement of a variable.
+    IncDec {
+        identifier: Symbol,
+        /// Either `Operator::PlusPlus` or `Operator::MinusMinus`.
+        operator: Operator,
+        prefix: bool,
+    },
     // Medium precedence
     Binary {
         left: Box<Expression>,
         operator: Operator,
+        /// Position of the operator token, where runtime errors are reported.
+        op_line: usize,
+        op_column: usize,
         right: Box<Expression>,
     },
     // Lower precedence
+    /// `and`, `or` or `??`, which only evaluate their right operand when needed.
     Logical {
         left: Box<Expression>,
         logic_op: TokenType,
+        /// Position of the operator token.
+        op_line: usize,
+        op_column: usize,
         right: Box<Expression>,
     },
-    // Lowest precedence
+    // Lower precedence
     Assignment {
-        identifier: String,
+        identifier: Symbol,
+        value: Box<Expression>,
+    },
+    /// `object[index] = value`, writing the entry of a map.
+    IndexAssignment {
+        object: Box<Expression>,
+        index: Box<Expression>,
+        value: Box<Expression>,
+    },
+    /// Assignment to the global binding of a name, skipping any local shadows.
+    GlobalAssignment {
+        identifier: Symbol,
         value: Box<Expression>,
     },
+    // Lowest precedence
+    /// Comma separated expressions evaluated left to right, yielding the last value.
+    Sequence {
+        expressions: Vec<Expression>,
+    },
+}
+
+impl Declaration {
+    /// Compares two declarations by structure alone, see `Expression::struct_eq`.
+    pub fn struct_eq(&self, other: &Declaration) -> bool {
+        match (&self.kind, &other.kind) {
+            (DeclKind::VarDecl(a), DeclKind::VarDecl(b)) => a.struct_eq(b),
+            (DeclKind::MultiVarDecl(a), DeclKind::MultiVarDecl(b)) => {
+                all_struct_eq(a, b, VarDecl::struct_eq)
+            }
+            (DeclKind::Statement(a), DeclKind::Statement(b)) => a.struct_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl VarDecl {
+    /// Compares two variable declarations by structure alone, see
+    /// `Expression::struct_eq`.
+    pub fn struct_eq(&self, other: &VarDecl) -> bool {
+        self.identifier == other.identifier
+            && self.constant == other.constant
+            && option_struct_eq(
+                self.initializer.as_ref(),
+                other.initializer.as_ref(),
+                Expression::struct_eq,
+            )
+    }
+}
+
+impl Statement {
+    /// Compares two statements by structure alone, see `Expression::struct_eq`.
+    pub fn struct_eq(&self, other: &Statement) -> bool {
+        match (&self.kind, &other.kind) {
+            (StmtKind::ExprStmt { expression: a }, StmtKind::ExprStmt { expression: b }) => {
+                a.struct_eq(b)
+            }
+            (
+                StmtKind::IfStmt {
+                    condition: a_condition,
+                    then_stmt: a_then,
+                    else_stmt: a_else,
+                },
+                StmtKind::IfStmt {
+                    condition: b_condition,
+                    then_stmt: b_then,
+                    else_stmt: b_else,
+                },
+            ) => {
+                a_condition.struct_eq(b_condition)
+                    && a_then.struct_eq(b_then)
+                    && option_struct_eq(a_else.as_deref(), b_else.as_deref(), Statement::struct_eq)
+            }
+            (
+                StmtKind::WhileStmt {
+                    condition: a_condition,
+                    do_stmt: a_body,
+                    label: a_label,
+                },
+                StmtKind::WhileStmt {
+                    condition: b_condition,
+                    do_stmt: b_body,
+                    label: b_label,
+                },
+            ) => {
+                a_label == b_label && a_condition.struct_eq(b_condition) && a_body.struct_eq(b_body)
+            }
+            (
+                StmtKind::ForStmt {
+                    initializer: a_init,
+                    condition: a_condition,
+                    update: a_update,
+                    body: a_body,
+                    label: a_label,
+                },
+                StmtKind::ForStmt {
+                    initializer: b_init,
+                    condition: b_condition,
+                    update: b_update,
+                    body: b_body,
+                    label: b_label,
+                },
+            ) => {
+                a_label == b_label
+                    && option_struct_eq(
+                        a_init.as_deref(),
+                        b_init.as_deref(),
+                        Declaration::struct_eq,
+                    )
+                    && option_struct_eq(
+                        a_condition.as_deref(),
+                        b_condition.as_deref(),
+                        Expression::struct_eq,
+                    )
+                    && option_struct_eq(
+                        a_update.as_deref(),
+                        b_update.as_deref(),
+                        Expression::struct_eq,
+                    )
+                    && a_body.struct_eq(b_body)
+            }
+            (StmtKind::PrintStmt { expressions: a }, StmtKind::PrintStmt { expressions: b }) => {
+                all_struct_eq(a, b, Expression::struct_eq)
+            }
+            (StmtKind::Break { label: a }, StmtKind::Break { label: b })
+            | (StmtKind::Continue { label: a }, StmtKind::Continue { label: b }) => a == b,
+            (
+                StmtKind::Switch {
+                    discriminant: a_discriminant,
+                    cases: a_cases,
+                    default: a_default,
+                },
+                StmtKind::Switch {
+                    discriminant: b_discriminant,
+                    cases: b_cases,
+                    default: b_default,
+                },
+            ) => {
+                a_discriminant.struct_eq(b_discriminant)
+                    && all_struct_eq(a_cases, b_cases, |a, b| {
+                        a.value.struct_eq(&b.value)
+                            && a.fallthrough == b.fallthrough
+                            && all_struct_eq(&a.body, &b.body, Declaration::struct_eq)
+                    })
+                    && option_struct_eq(a_default.as_ref(), b_default.as_ref(), |a, b| {
+                        all_struct_eq(a, b, Declaration::struct_eq)
+                    })
+            }
+            (StmtKind::Block { declarations: a }, StmtKind::Block { declarations: b }) => {
+                all_struct_eq(a, b, Declaration::struct_eq)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Expression {
+    /// Compares two expressions by structure alone, so that parsing the same
+    /// program from differently formatted source gives equal trees.
+    ///
+    /// - Positions, of nodes and of operators, are ignored.
+    /// - Groupings are looked through: the parentheses they record are already
+    ///   expressed by the shape of the tree, so `(1 + 2) * 3` and `((1 + 2)) * 3`
+    ///   are equal.
+    /// - Number literals are compared by their bits, so a `NaN` literal equals
+    ///   itself.
+    pub fn struct_eq(&self, other: &Expression) -> bool {
+        match (&self.ungrouped().kind, &other.ungrouped().kind) {
+            (ExprKind::Lit { value: a }, ExprKind::Lit { value: b }) => match (a, b) {
+                (Literal::Number(a), Literal::Number(b)) => a.to_bits() == b.to_bits(),
+                _ => a == b,
+            },
+            (ExprKind::Var { identifier: a }, ExprKind::Var { identifier: b }) => a == b,
+            (
+                ExprKind::BlockExpr {
+                    declarations: a_declarations,
+                    tail: a_tail,
+                },
+                ExprKind::BlockExpr {
+                    declarations: b_declarations,
+                    tail: b_tail,
+                },
+            ) => {
+                all_struct_eq(a_declarations, b_declarations, Declaration::struct_eq)
+                    && option_struct_eq(a_tail.as_deref(), b_tail.as_deref(), Expression::struct_eq)
+            }
+            (ExprKind::MapLit { entries: a }, ExprKind::MapLit { entries: b }) => {
+                all_struct_eq(a, b, |(a_key, a_value), (b_key, b_value)| {
+                    a_key.struct_eq(b_key) && a_value.struct_eq(b_value)
+                })
+            }
+            (
+                ExprKind::Index {
+                    object: a_object,
+                    index: a_index,
+                },
+                ExprKind::Index {
+                    object: b_object,
+                    index: b_index,
+                },
+            ) => a_object.struct_eq(b_object) && a_index.struct_eq(b_index),
+            (
+                ExprKind::Unary {
+                    operator: a_operator,
+                    right: a_right,
+                },
+                ExprKind::Unary {
+                    operator: b_operator,
+                    right: b_right,
+                },
+            ) => a_operator == b_operator && a_right.struct_eq(b_right),
+            (ExprKind::TypeOf { operand: a }, ExprKind::TypeOf { operand: b }) => a.struct_eq(b),
+            (
+                ExprKind::IncDec {
+                    identifier: a_identifier,
+                    operator: a_operator,
+                    prefix: a_prefix,
+                },
+                ExprKind::IncDec {
+                    identifier: b_identifier,
+                    operator: b_operator,
+                    prefix: b_prefix,
+                },
+            ) => a_identifier == b_identifier && a_operator == b_operator && a_prefix == b_prefix,
+            (
+                ExprKind::Binary {
+                    left: a_left,
+                    operator: a_operator,
+                    right: a_right,
+                    ..
+                },
+                ExprKind::Binary {
+                    left: b_left,
+                    operator: b_operator,
+                    right: b_right,
+                    ..
+                },
+            ) => a_operator == b_operator && a_left.struct_eq(b_left) && a_right.struct_eq(b_right),
+            (
+                ExprKind::Logical {
+                    left: a_left,
+                    logic_op: a_operator,
+                    right: a_right,
+                    ..
+                },
+                ExprKind::Logical {
+                    left: b_left,
+                    logic_op: b_operator,
+                    right: b_right,
+                    ..
+                },
+            ) => a_operator == b_operator && a_left.struct_eq(b_left) && a_right.struct_eq(b_right),
+            (
+                ExprKind::Assignment {
+                    identifier: a_identifier,
+                    value: a_value,
+                },
+                ExprKind::Assignment {
+                    identifier: b_identifier,
+                    value: b_value,
+                },
+            )
+            | (
+                ExprKind::GlobalAssignment {
+                    identifier: a_identifier,
+                    value: a_value,
+                },
+                ExprKind::GlobalAssignment {
+                    identifier: b_identifier,
+                    value: b_value,
+                },
+            ) => a_identifier == b_identifier && a_value.struct_eq(b_value),
+            (
+                ExprKind::IndexAssignment {
+                    object: a_object,
+                    index: a_index,
+                    value: a_value,
+                },
+                ExprKind::IndexAssignment {
+                    object: b_object,
+                    index: b_index,
+                    value: b_value,
+                },
+            ) => {
+                a_object.struct_eq(b_object)
+                    && a_index.struct_eq(b_index)
+                    && a_value.struct_eq(b_value)
+            }
+            (ExprKind::Sequence { expressions: a }, ExprKind::Sequence { expressions: b }) => {
+                all_struct_eq(a, b, Expression::struct_eq)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the expression inside any groupings wrapped around it.
+    fn ungrouped(&self) -> &Expression {
+        match &self.kind {
+            ExprKind::Grouping { expression } => expression.ungrouped(),
+            _ => self,
+        }
+    }
+}
+
+/// Compares two lists element by element with `eq`.
+fn all_struct_eq<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| eq(a, b))
+}
+
+/// Compares two optional nodes with `eq`, where two absent nodes are equal.
+fn option_struct_eq<T>(a: Option<&T>, b: Option<&T>, eq: impl Fn(&T, &T) -> bool) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Read-only traversal of the AST, producing a `T` for every node visited.
+///
+/// Every method defaults to walking into the node's children through the
+/// matching `walk_*` function, so a pass only overrides the nodes it cares
+/// about. An override that still wants the children visited calls the
+/// `walk_*` function itself, before or after its own work.
+///
+/// The `walk_*` functions visit the children for their effect and return
+/// `T::default()`, which suits passes that only collect state, such as the
+/// linter with the default `T = ()`. A pass building a value out of its
+/// children's, such as the `PrettyPrinter` producing `String`s, overrides
+/// the methods of every node kind and combines the results itself.
+pub trait Visitor<T: Default = ()>: Sized {
+    fn visit_decl(&mut self, declaration: &Declaration) -> T {
+        walk_decl(self, declaration)
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> T {
+        walk_var_decl(self, var_decl)
+    }
+
+    fn visit_stmt(&mut self, statement: &Statement) -> T {
+        walk_stmt(self, statement)
+    }
+
+    fn visit_expr(&mut self, expression: &Expression) -> T {
+        walk_expr(self, expression)
+    }
+}
+
+/// Visits every top-level declaration of a program in order.
+pub fn walk_program<T: Default, V: Visitor<T>>(visitor: &mut V, program: &[Declaration]) -> T {
+    for declaration in program {
+        visitor.visit_decl(declaration);
+    }
+    T::default()
+}
+
+pub fn walk_decl<T: Default, V: Visitor<T>>(visitor: &mut V, declaration: &Declaration) -> T {
+    match &declaration.kind {
+        DeclKind::VarDecl(var_decl) => {
+            visitor.visit_var_decl(var_decl);
+        }
+        DeclKind::MultiVarDecl(var_decls) => {
+            for var_decl in var_decls {
+                visitor.visit_var_decl(var_decl);
+            }
+        }
+        DeclKind::Statement(statement) => {
+            visitor.visit_stmt(statement);
+        }
+    }
+    T::default()
+}
+
+pub fn walk_var_decl<T: Default, V: Visitor<T>>(visitor: &mut V, var_decl: &VarDecl) -> T {
+    if let Some(initializer) = &var_decl.initializer {
+        visitor.visit_expr(initializer);
+    }
+    T::default()
+}
+
+/// Visits the children of a statement in the order they are evaluated.
+pub fn walk_stmt<T: Default, V: Visitor<T>>(visitor: &mut V, statement: &Statement) -> T {
+    match &statement.kind {
+        StmtKind::ExprStmt { expression } => {
+            visitor.visit_expr(expression);
+        }
+        StmtKind::PrintStmt { expressions } => {
+            for expression in expressions {
+                visitor.visit_expr(expression);
+            }
+        }
+        StmtKind::IfStmt {
+            condition,
+            then_stmt,
+            else_stmt,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_stmt);
+            if let Some(stmt) = else_stmt {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        StmtKind::WhileStmt {
+            condition, do_stmt, ..
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(do_stmt);
+        }
+        StmtKind::ForStmt {
+            initializer,
+            condition,
+            update,
+            body,
+            ..
+        } => {
+            if let Some(init) = initializer {
+                visitor.visit_decl(init);
+            }
+            if let Some(cond) = condition {
+                visitor.visit_expr(cond);
+            }
+            visitor.visit_stmt(body);
+            if let Some(upd) = update {
+                visitor.visit_expr(upd);
+            }
+        }
+        StmtKind::Block { declarations } => {
+            walk_program(visitor, declarations);
+        }
+        StmtKind::Switch {
+            discriminant,
+            cases,
+            default,
+        } => {
+            visitor.visit_expr(discriminant);
+            for case in cases {
+                visitor.visit_expr(&case.value);
+                walk_program(visitor, &case.body);
+            }
+            if let Some(body) = default {
+                walk_program(visitor, body);
+            }
+        }
+        StmtKind::Break { .. } | StmtKind::Continue { .. } => {}
+    }
+    T::default()
+}
+
+pub fn walk_expr<T: Default, V: Visitor<T>>(visitor: &mut V, expression: &Expression) -> T {
+    match &expression.kind {
+        ExprKind::Lit { .. } | ExprKind::Var { .. } | ExprKind::IncDec { .. } => {}
+        ExprKind::Grouping { expression } => {
+            visitor.visit_expr(expression);
+        }
+        ExprKind::BlockExpr { declarations, tail } => {
+            walk_program(visitor, declarations);
+            if let Some(tail) = tail {
+                visitor.visit_expr(tail);
+            }
+        }
+        ExprKind::MapLit { entries } => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Index { object, index } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+        ExprKind::IndexAssignment {
+            object,
+            index,
+            value,
+        } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        }
+        ExprKind::Unary { right, .. } => {
+            visitor.visit_expr(right);
+        }
+        ExprKind::TypeOf { operand } => {
+            visitor.visit_expr(operand);
+        }
+        ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Assignment { value, .. } | ExprKind::GlobalAssignment { value, .. } => {
+            visitor.visit_expr(value);
+        }
+        ExprKind::Sequence { expressions } => {
+            for expression in expressions {
+                visitor.visit_expr(expression);
+            }
+        }
+    }
+    T::default()
 }
@@ -0,0 +1,251 @@
+//! Implements constant folding for Lox programs.
+//!
+//! This module contains the `ConstantFolder` struct, an optional pass that
+//! replaces operators applied only to literals with the literal they evaluate
+//! to, so `60 * 60 * 24` is computed once before the program runs, and drops
+//! the branches of `if` and `while` statements that can never run.
+use crate::ast::{DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind};
+use crate::error_reporter::ErrorReporter;
+use crate::interpreter::{Interpreter, Value};
+
+/// AST pass folding constant `Unary`, `Binary` and `Grouping` expressions.
+///
+/// Candidates are evaluated by an interpreter of their own, so folding follows
+/// exactly the runtime's type rules. Anything that would report an error, or
+/// produces an infinite or NaN number (such as `Infinity - Infinity`), is left in place so
+/// that the runtime still deals with it at the right position. Variables are
+/// never folded.
+///
+/// An `if` whose condition is, or folds to, the literal `true` or `false` is
+/// replaced by the branch it takes, or an empty block if there is none, and
+/// a `while (false)` loop by an empty block. Other conditions, such as `nil`
+/// or anything involving a variable, are left alone.
+pub struct ConstantFolder {
+    /// Evaluates candidate nodes; its diagnostics are collected, never shown.
+    interpreter: Interpreter,
+}
+
+impl Default for ConstantFolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantFolder {
+    /// Creates a new ConstantFolder instance
+    pub fn new() -> Self {
+        let mut interpreter = Interpreter::new();
+        interpreter.error_reporter = ErrorReporter::collecting();
+        ConstantFolder { interpreter }
+    }
+
+    pub fn fold_program(&mut self, program: &mut Program) {
+        for declaration in program {
+            self.fold_declaration(declaration);
+        }
+    }
+
+    fn fold_declaration(&mut self, declaration: &mut Declaration) {
+        match &mut declaration.kind {
+            DeclKind::VarDecl(var_decl) => {
+                if let Some(initializer) = &mut var_decl.initializer {
+                    self.fold_expression(initializer);
+                }
+            }
+            DeclKind::MultiVarDecl(var_decls) => {
+                for initializer in var_decls.iter_mut().filter_map(|v| v.initializer.as_mut()) {
+                    self.fold_expression(initializer);
+                }
+            }
+            DeclKind::Statement(statement) => self.fold_statement(statement),
+        }
+    }
+
+    fn fold_statement(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StmtKind::ExprStmt { expression } => self.fold_expression(expression),
+            StmtKind::PrintStmt { expressions } => {
+                for expression in expressions {
+                    self.fold_expression(expression);
+                }
+            }
+            StmtKind::IfStmt {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.fold_expression(condition);
+                self.fold_statement(then_stmt);
+                if let Some(stmt) = else_stmt {
+                    self.fold_statement(stmt);
+                }
+            }
+            StmtKind::WhileStmt {
+                condition, do_stmt, ..
+            } => {
+                self.fold_expression(condition);
+                self.fold_statement(do_stmt);
+            }
+            StmtKind::ForStmt {
+                initializer,
+                condition,
+                update,
+                body,
+                ..
+            } => {
+                if let Some(init) = initializer {
+                    self.fold_declaration(init);
+                }
+                if let Some(cond) = condition {
+                    self.fold_expression(cond);
+                }
+                if let Some(upd) = update {
+                    self.fold_expression(upd);
+                }
+                self.fold_statement(body);
+            }
+            StmtKind::Block { declarations } => self.fold_declarations(declarations),
+            StmtKind::Switch {
+                discriminant,
+                cases,
+                default,
+            } => {
+                self.fold_expression(discriminant);
+                for case in cases {
+                    self.fold_expression(&mut case.value);
+                    self.fold_declarations(&mut case.body);
+                }
+                if let Some(body) = default {
+                    self.fold_declarations(body);
+                }
+            }
+            StmtKind::Break { .. } | StmtKind::Continue { .. } => {}
+        }
+        if let Some(taken) = Self::taken_branch(statement) {
+            *statement = taken;
+        }
+    }
+
+    /// Returns what an `if` or `while` statement with a literal boolean
+    /// condition reduces to, taking the branch out of it, or `None` if the
+    /// statement is kept.
+    fn taken_branch(statement: &mut Statement) -> Option<Statement> {
+        let empty_block = Statement {
+            kind: StmtKind::Block {
+                declarations: vec![],
+            },
+            line: statement.line,
+            column: statement.column,
+        };
+        match &mut statement.kind {
+            StmtKind::IfStmt {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => match boolean_literal(condition)? {
+                true => Some(std::mem::replace(then_stmt.as_mut(), empty_block)),
+                false => Some(else_stmt.take().map_or(empty_block, |stmt| *stmt)),
+            },
+            StmtKind::WhileStmt { condition, .. } if boolean_literal(condition) == Some(false) => {
+                Some(empty_block)
+            }
+            _ => None,
+        }
+    }
+
+    fn fold_declarations(&mut self, declarations: &mut [Declaration]) {
+        for declaration in declarations {
+            self.fold_declaration(declaration);
+        }
+    }
+
+    /// Folds the operands of an expression first, then the expression itself.
+    pub fn fold_expression(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExprKind::Lit { .. } | ExprKind::Var { .. } | ExprKind::IncDec { .. } => {}
+            ExprKind::Grouping { expression } => self.fold_expression(expression),
+            ExprKind::BlockExpr { declarations, tail } => {
+                self.fold_declarations(declarations);
+                if let Some(tail) = tail {
+                    self.fold_expression(tail);
+                }
+            }
+            ExprKind::MapLit { entries } => {
+                for (key, value) in entries {
+                    self.fold_expression(key);
+                    self.fold_expression(value);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.fold_expression(object);
+                self.fold_expression(index);
+            }
+            ExprKind::IndexAssignment {
+                object,
+                index,
+                value,
+            } => {
+                self.fold_expression(object);
+                self.fold_expression(index);
+                self.fold_expression(value);
+            }
+            ExprKind::Unary { right, .. } => self.fold_expression(right),
+            ExprKind::TypeOf { operand } => self.fold_expression(operand),
+            ExprKind::Binary { left, right, .. } | ExprKind::Logical { left, right, .. } => {
+                self.fold_expression(left);
+                self.fold_expression(right);
+            }
+            ExprKind::Assignment { value, .. } | ExprKind::GlobalAssignment { value, .. } => {
+                self.fold_expression(value)
+            }
+            ExprKind::Sequence { expressions } => {
+                for expression in expressions {
+                    self.fold_expression(expression);
+                }
+            }
+        }
+        if let Some(value) = self.constant_value(expression) {
+            expression.kind = ExprKind::Lit { value };
+        }
+    }
+
+    /// Returns the value of an operator node whose operands are all literals,
+    /// or `None` if it should be left for the runtime.
+    fn constant_value(&mut self, expression: &Expression) -> Option<Value> {
+        let operands_are_literals = match &expression.kind {
+            ExprKind::Grouping { expression } => is_literal(expression),
+            ExprKind::Unary { right, .. } => is_literal(right),
+            ExprKind::Binary { left, right, .. } => is_literal(left) && is_literal(right),
+            _ => false,
+        };
+        if !operands_are_literals {
+            return None;
+        }
+        let value = self.interpreter.evaluate_expression(expression).ok()?;
+        let reported_error = !self
+            .interpreter
+            .error_reporter
+            .take_diagnostics()
+            .is_empty();
+        match value {
+            _ if reported_error => None,
+            Value::Number(n) if !n.is_finite() => None,
+            value => Some(value),
+        }
+    }
+}
+
+fn is_literal(expression: &Expression) -> bool {
+    matches!(expression.kind, ExprKind::Lit { .. })
+}
+
+/// Returns the value of a `true` or `false` literal, or `None` for any other
+/// expression.
+fn boolean_literal(expression: &Expression) -> Option<bool> {
+    match expression.kind {
+        ExprKind::Lit {
+            value: Value::Boolean(b),
+        } => Some(b),
+        _ => None,
+    }
+}
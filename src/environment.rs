@@ -1,68 +1,247 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+};
 
-use crate::{error_reporter::RuntimeError, token::Literal};
+use crate::{interner::Symbol, token::Literal};
+
+/// Why a variable could not be read or written, or a scope not removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentError {
+    UndefinedVariable,
+    UnInitializedVariable,
+    /// Assigning to a variable declared with `const`.
+    ConstantVariable,
+    CannotReduceGlobalScope,
+}
+
+/// How many emptied scopes `reduce_scope` keeps around for reuse.
+const MAX_POOLED_SCOPES: usize = 64;
 
 #[derive(Debug, Clone)]
 pub struct Environment {
-    scopes: Vec<HashMap<String, VariableState>>,
+    scopes: Vec<HashMap<Symbol, VariableState>>,
+    /// Emptied scopes kept for `increase_scope`, so entering a block does not
+    /// allocate a new map each time.
+    pool: Vec<HashMap<Symbol, VariableState>>,
 }
 
 #[derive(Debug, Clone)]
 pub enum VariableState {
     Uninitialized,
     Initialized(Literal),
+    /// A `const` variable, which keeps the value it was declared with.
+    Constant(Literal),
+}
+
+impl Display for VariableState {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            VariableState::Uninitialized => write!(f, "<uninitialized>"),
+            VariableState::Initialized(value) | VariableState::Constant(value) => {
+                write!(f, "{}", value)
+            }
+        }
+    }
+}
+
+impl VariableState {
+    fn value(&self) -> Result<Literal, EnvironmentError> {
+        match self {
+            VariableState::Initialized(value) | VariableState::Constant(value) => Ok(value.clone()),
+            VariableState::Uninitialized => Err(EnvironmentError::UnInitializedVariable),
+        }
+    }
+
+    fn assign(&mut self, value: Literal) -> Result<(), EnvironmentError> {
+        match self {
+            VariableState::Constant(_) => Err(EnvironmentError::ConstantVariable),
+            _ => {
+                *self = VariableState::Initialized(value);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
             scopes: vec![HashMap::new()],
+            pool: vec![],
         }
     }
 
+    /// Enters a new local scope, reusing a pooled map when one is available.
     pub fn increase_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        let scope = self.pool.pop().unwrap_or_default();
+        self.scopes.push(scope);
     }
 
-    pub fn reduce_scope(&mut self) -> Result<(), RuntimeError> {
+    /// Leaves the innermost local scope, returning its emptied map to the pool.
+    pub fn reduce_scope(&mut self) -> Result<(), EnvironmentError> {
         if self.scopes.len() > 1 {
-            self.scopes.pop();
+            if let Some(mut scope) = self.scopes.pop() {
+                if self.pool.len() < MAX_POOLED_SCOPES {
+                    scope.clear();
+                    self.pool.push(scope);
+                }
+            }
             Ok(())
         } else {
-            Err(RuntimeError::CannotReduceGlobalScope)
+            Err(EnvironmentError::CannotReduceGlobalScope)
         }
     }
 
-    pub fn define(&mut self, identifier: String, value: Option<Literal>) {
+    /// Removes every binding from the innermost local scope so it can be
+    /// reused, keeping its allocation. The global scope is never cleared.
+    pub fn clear_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.clear();
+            }
+        }
+    }
+
+    /// Binds `identifier` in the innermost scope, replacing any binding of the
+    /// same name there.
+    ///
+    /// Returns whether such a binding existed, so callers can warn about the
+    /// redefinition without looking it up first.
+    pub fn define(&mut self, identifier: Symbol, value: Option<Literal>) -> bool {
         let state = match value {
             Some(lit) => VariableState::Initialized(lit),
             None => VariableState::Uninitialized,
         };
-        self.scopes.last_mut().unwrap().insert(identifier, state);
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(identifier, state)
+            .is_some()
+    }
+
+    /// Binds `identifier` as a constant in the innermost scope, like `define`.
+    pub fn define_constant(&mut self, identifier: Symbol, value: Literal) -> bool {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(identifier, VariableState::Constant(value))
+            .is_some()
     }
 
-    pub fn get(&self, identifier: &str) -> Result<Literal, RuntimeError> {
+    pub fn get(&self, identifier: Symbol) -> Result<Literal, EnvironmentError> {
         for scope in self.scopes.iter().rev() {
-            if let Some(state) = scope.get(identifier) {
-                match state {
-                    VariableState::Initialized(value) => return Ok(value.clone()),
-                    VariableState::Uninitialized => {
-                        return Err(RuntimeError::UnInitializedVariable)
-                    }
-                }
+            if let Some(state) = scope.get(&identifier) {
+                return state.value();
             }
         }
-        Err(RuntimeError::UndefinedVariable)
+        Err(EnvironmentError::UndefinedVariable)
     }
 
-    pub fn assign(&mut self, identifier: &str, value: Literal) -> Result<(), RuntimeError> {
+    pub fn assign(&mut self, identifier: Symbol, value: Literal) -> Result<(), EnvironmentError> {
         for scope in self.scopes.iter_mut().rev() {
-            if scope.contains_key(identifier) {
-                scope.insert(identifier.to_string(), VariableState::Initialized(value));
-                return Ok(());
+            if let Some(state) = scope.get_mut(&identifier) {
+                return state.assign(value);
             }
         }
-        Err(RuntimeError::UndefinedVariable)
+        Err(EnvironmentError::UndefinedVariable)
+    }
+
+    /// Reads `identifier` from the scope `depth` levels out from the innermost
+    /// one, without probing the scopes in between.
+    ///
+    /// Meant for lookups whose depth was resolved ahead of time; `get` remains
+    /// the fallback that searches every scope.
+    pub fn get_at(&self, depth: usize, identifier: Symbol) -> Result<Literal, EnvironmentError> {
+        match self
+            .ancestor(depth)
+            .and_then(|scope| scope.get(&identifier))
+        {
+            Some(state) => state.value(),
+            None => Err(EnvironmentError::UndefinedVariable),
+        }
+    }
+
+    /// Assigns to `identifier` in the scope `depth` levels out from the
+    /// innermost one, without probing the scopes in between.
+    pub fn assign_at(
+        &mut self,
+        depth: usize,
+        identifier: Symbol,
+        value: Literal,
+    ) -> Result<(), EnvironmentError> {
+        match self
+            .ancestor_mut(depth)
+            .and_then(|scope| scope.get_mut(&identifier))
+        {
+            Some(state) => state.assign(value),
+            None => Err(EnvironmentError::UndefinedVariable),
+        }
+    }
+
+    /// Returns the scope `depth` levels out, where 0 is the innermost scope.
+    fn ancestor(&self, depth: usize) -> Option<&HashMap<Symbol, VariableState>> {
+        let index = self.scopes.len().checked_sub(depth + 1)?;
+        self.scopes.get(index)
+    }
+
+    fn ancestor_mut(&mut self, depth: usize) -> Option<&mut HashMap<Symbol, VariableState>> {
+        let index = self.scopes.len().checked_sub(depth + 1)?;
+        self.scopes.get_mut(index)
+    }
+
+    /// Iterates over the bindings of the innermost scope, in no particular order.
+    pub fn bindings(&self) -> impl Iterator<Item = (Symbol, &VariableState)> {
+        self.scopes
+            .last()
+            .into_iter()
+            .flat_map(|scope| scope.iter())
+            .map(|(identifier, state)| (*identifier, state))
+    }
+
+    /// Renders every visible binding as `name = value`, one per line.
+    ///
+    /// Scopes are listed from the innermost outward, each sorted by name, and
+    /// bindings shadowed by an inner scope are left out.
+    pub fn dump(&self) -> String {
+        let mut seen = HashSet::new();
+        let mut lines = vec![];
+        for scope in self.scopes.iter().rev() {
+            let mut visible: Vec<_> = scope
+                .iter()
+                .filter(|(identifier, _)| seen.insert(**identifier))
+                .map(|(identifier, state)| (identifier.to_string(), state))
+                .collect();
+            visible.sort_by(|(a, _), (b, _)| a.cmp(b));
+            lines.extend(
+                visible
+                    .into_iter()
+                    .map(|(name, state)| format!("{} = {}", name, state)),
+            );
+        }
+        lines.join("\n")
     }
-}
 
+    /// Assigns to the global binding of `identifier`, ignoring any local
+    /// bindings that shadow it.
+    pub fn assign_at_global(
+        &mut self,
+        identifier: Symbol,
+        value: Literal,
+    ) -> Result<(), EnvironmentError> {
+        match self
+            .scopes
+            .first_mut()
+            .and_then(|globals| globals.get_mut(&identifier))
+        {
+            Some(state) => state.assign(value),
+            None => Err(EnvironmentError::UndefinedVariable),
+        }
+    }
+}
@@ -4,23 +4,252 @@
 //! tracking and reporting errors during the interpretation process
 //! without crashing or panicing.
 
+use crate::{
+    interner::Symbol,
+    token::{Operator, Token, TokenType},
+};
+use std::{
+    fmt::{Display, Formatter, Result},
+    rc::Rc,
+};
+
 /// Represents errors that can occur during parsing.
 pub enum ParseError {
     UnexpectedToken,
+    /// An expected token is missing because the input ended.
     MissingToken,
     UnexpectedEOF,
 }
 
+impl ParseError {
+    /// Returns whether the error only means the input ended too early, so
+    /// more input could still complete it.
+    pub fn is_end_of_input(&self) -> bool {
+        matches!(self, ParseError::MissingToken | ParseError::UnexpectedEOF)
+    }
+}
+
+/// A runtime error that stops evaluation, carrying the position it is
+/// reported at.
+///
+/// The message is the error's `Display`; `ErrorReporter::runtime_error`
+/// reports it at its position.
+#[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeError {
-    UndefinedVariable,
-    UnInitializedVariable,
-    CannotReduceGlobalScope,
+    /// An error was reported while evaluation carried on with a stand-in
+    /// value, as returned by `Interpreter::eval_expression`.
+    Reported,
+    /// An operator or index applied to a value of the wrong type, such as
+    /// `1 + nil`. `length` is the number of characters to underline.
+    TypeMismatch {
+        line: usize,
+        column: usize,
+        length: usize,
+        message: String,
+    },
+    /// A result too large to represent, such as `10 ** 400` or `1 << 63`.
+    Overflow {
+        line: usize,
+        column: usize,
+        length: usize,
+        message: String,
+    },
+    /// Dividing by zero, such as `1 / 0` or `0 / 0`.
+    DivisionByZero { line: usize, column: usize },
+    UndefinedVariable {
+        name: Symbol,
+        line: usize,
+        column: usize,
+    },
+    /// Assigning to a variable declared with `const`.
+    ConstantAssignment {
+        name: Symbol,
+        line: usize,
+        column: usize,
+    },
+    /// Reading a declared but unassigned variable, only fatal in strict mode.
+    UnInitializedVariable {
+        name: Symbol,
+        line: usize,
+        column: usize,
+    },
+    StackOverflow {
+        line: usize,
+        column: usize,
+        max_depth: usize,
+    },
+    LoopLimitExceeded {
+        line: usize,
+        column: usize,
+        limit: usize,
+    },
+    /// A `break` or `continue` trying to leave a block expression.
+    InvalidControlFlow { line: usize, column: usize },
+}
+
+impl RuntimeError {
+    /// Returns the line and column the error is reported at, or `None` for
+    /// `Reported`, which has been reported already.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            RuntimeError::Reported => None,
+            RuntimeError::TypeMismatch { line, column, .. }
+            | RuntimeError::Overflow { line, column, .. }
+            | RuntimeError::DivisionByZero { line, column }
+            | RuntimeError::UndefinedVariable { line, column, .. }
+            | RuntimeError::ConstantAssignment { line, column, .. }
+            | RuntimeError::UnInitializedVariable { line, column, .. }
+            | RuntimeError::StackOverflow { line, column, .. }
+            | RuntimeError::LoopLimitExceeded { line, column, .. }
+            | RuntimeError::InvalidControlFlow { line, column } => Some((*line, *column)),
+        }
+    }
+
+    /// Returns the number of characters of source the error spans.
+    pub fn length(&self) -> usize {
+        match self {
+            RuntimeError::TypeMismatch { length, .. } | RuntimeError::Overflow { length, .. } => {
+                *length
+            }
+            RuntimeError::UndefinedVariable { name, .. }
+            | RuntimeError::ConstantAssignment { name, .. }
+            | RuntimeError::UnInitializedVariable { name, .. } => {
+                name.with_str(|name| name.chars().count())
+            }
+            _ => 1,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            RuntimeError::Reported => write!(f, "Runtime error."),
+            RuntimeError::TypeMismatch { message, .. } | RuntimeError::Overflow { message, .. } => {
+                write!(f, "{}", message)
+            }
+            RuntimeError::DivisionByZero { .. } => write!(f, "Division by zero."),
+            RuntimeError::UndefinedVariable { name, .. } => {
+                write!(f, "Undefined Variable: {}", name)
+            }
+            RuntimeError::ConstantAssignment { name, .. } => {
+                write!(f, "Cannot assign to constant '{}'.", name)
+            }
+            RuntimeError::UnInitializedVariable { name, .. } => {
+                write!(f, "Uninitialized Variable: {}", name)
+            }
+            RuntimeError::StackOverflow { max_depth, .. } => write!(
+                f,
+                "Stack overflow: evaluation nested deeper than {} levels.",
+                max_depth
+            ),
+            RuntimeError::LoopLimitExceeded { limit, .. } => {
+                write!(f, "Loop limit exceeded: more than {} iterations.", limit)
+            }
+            RuntimeError::InvalidControlFlow { .. } => write!(
+                f,
+                "Cannot use 'break' or 'continue' to leave a block expression."
+            ),
+        }
+    }
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Information about the reporting itself, such as errors being dropped.
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Note => write!(f, "Note"),
+        }
+    }
 }
 
+/// A single error or warning, as collected by a collecting `ErrorReporter`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    /// Number of characters the diagnostic spans from its column, if known.
+    pub length: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "[Line {}, Column {}] {}: {}",
+            self.line, self.column, self.severity, self.message
+        )
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic as a single-line JSON object, tagged with the
+    /// phase of the interpreter that reported it, e.g.
+    /// `{"line":1,"column":7,"severity":"error","phase":"parse","message":"..."}`.
+    pub fn to_json(&self, phase: &str) -> String {
+        format!(
+            "{{\"line\":{},\"column\":{},\"severity\":\"{}\",\"phase\":\"{}\",\"message\":\"{}\"}}",
+            self.line,
+            self.column,
+            self.severity.to_string().to_lowercase(),
+            escape_json(phase),
+            escape_json(&self.message)
+        )
+    }
+}
+
+/// Escapes `text` for use inside a JSON string literal.
+pub(crate) fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The default for `ErrorReporter::max_errors`.
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
 /// A struct for reporting and tracking errors in the Lox interpreter.
 pub struct ErrorReporter {
     /// Indicates whether an error has been encountered.
     had_error: bool,
+    /// Indicates whether a runtime error has been encountered.
+    had_runtime_error: bool,
+    /// Number of errors reported so far, including those beyond the limit.
+    error_count: usize,
+    /// Number of errors after which further errors are no longer printed.
+    max_errors: usize,
+    /// Diagnostics kept instead of printed, or `None` if they are printed.
+    collected: Option<Vec<Diagnostic>>,
+    /// The source being reported on, used to show the offending line.
+    source: Option<Rc<str>>,
+}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ErrorReporter {
@@ -28,7 +257,56 @@ impl ErrorReporter {
     ///
     /// Initializes with no errors reported.
     pub fn new() -> Self {
-        ErrorReporter { had_error: false }
+        ErrorReporter {
+            had_error: false,
+            had_runtime_error: false,
+            error_count: 0,
+            max_errors: DEFAULT_MAX_ERRORS,
+            collected: None,
+            source: None,
+        }
+    }
+
+    /// Creates an `ErrorReporter` that keeps its diagnostics instead of
+    /// printing them, to be retrieved with `take_diagnostics`.
+    ///
+    /// The error limit applies as when printing; the note about it is kept as
+    /// a diagnostic of severity `Note`.
+    pub fn collecting() -> Self {
+        ErrorReporter {
+            collected: Some(vec![]),
+            ..ErrorReporter::new()
+        }
+    }
+
+    /// Removes and returns the diagnostics collected so far.
+    ///
+    /// Always empty for a reporter that prints its diagnostics.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.collected
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Attaches the source code the diagnostics refer to.
+    ///
+    /// Printed diagnostics are then followed by the offending source line,
+    /// underlined at their position: `^~~~` across a known span, or a single
+    /// `^` otherwise.
+    pub fn with_source(mut self, source: Rc<str>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Sets how many errors are printed before the reporter stops.
+    ///
+    /// Errors beyond the limit are only counted, with a single note on the
+    /// first one dropped, so one mistake cannot bury the output in cascading
+    /// errors.
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
     }
 
     /// Reports an error at a specific line and column.
@@ -39,21 +317,207 @@ impl ErrorReporter {
     /// * `column` - The column number where the error occurred.
     /// * `message` - The error message to report.
     pub fn error(&mut self, line: usize, column: usize, message: &str) {
-        self.report(line, column, "", message);
+        self.report_error(line, column, None, message);
     }
 
-    /// Internal method to format and print the error message.
-    /// Also sets the `had_error` flag to true.
-    fn report(&mut self, line: usize, column: usize, loc: &str, message: &str) {
-        eprintln!(
-            "[Line {}, Column {}] Error{}: {}",
-            line, column, loc, message
-        );
+    /// Reports an error spanning `length` characters from a specific line and
+    /// column, such as a whole token.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line number where the error occurred.
+    /// * `column` - The column number where the span starts.
+    /// * `length` - The number of characters the span covers.
+    /// * `message` - The error message to report.
+    pub fn error_spanning(&mut self, line: usize, column: usize, length: usize, message: &str) {
+        self.report_error(line, column, Some(length), message);
+    }
+
+    fn report_error(&mut self, line: usize, column: usize, length: Option<usize>, message: &str) {
         self.had_error = true;
+        self.error_count += 1;
+        if self.error_count <= self.max_errors {
+            self.report(line, column, length, Severity::Error, message);
+        } else if self.error_count - 1 == self.max_errors {
+            self.report_limit();
+        }
+    }
+
+    /// Notes that the error limit was exceeded and later errors are dropped.
+    fn report_limit(&mut self) {
+        let message = format!("Too many errors ({}), stopping.", self.max_errors);
+        match &mut self.collected {
+            Some(collected) => collected.push(Diagnostic {
+                severity: Severity::Note,
+                line: 0,
+                column: 0,
+                length: None,
+                message,
+            }),
+            None => eprintln!("{}", message),
+        }
+    }
+
+    /// Reports a runtime error at its position, unless it has been reported already.
+    ///
+    /// Either way the runtime-error flag is set.
+    pub fn runtime_error(&mut self, error: &RuntimeError) {
+        self.had_runtime_error = true;
+        if let Some((line, column)) = error.position() {
+            self.error_spanning(line, column, error.length(), &error.to_string());
+        }
+    }
+
+    /// Reports a warning at a specific line and column.
+    ///
+    /// Warnings are printed like errors but do not set the `had_error` flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line number the warning refers to.
+    /// * `column` - The column number the warning refers to.
+    /// * `message` - The warning message to report.
+    pub fn warning(&mut self, line: usize, column: usize, message: &str) {
+        self.report(line, column, None, Severity::Warning, message);
+    }
+
+    /// Internal method to print or collect a diagnostic.
+    fn report(
+        &mut self,
+        line: usize,
+        column: usize,
+        length: Option<usize>,
+        severity: Severity,
+        message: &str,
+    ) {
+        let diagnostic = Diagnostic {
+            severity,
+            line,
+            column,
+            length,
+            message: message.to_string(),
+        };
+        match &mut self.collected {
+            Some(collected) => collected.push(diagnostic),
+            None => {
+                eprintln!("{}", diagnostic);
+                if let Some(excerpt) = self.excerpt(&diagnostic) {
+                    eprintln!("{}", excerpt);
+                }
+            }
+        }
+    }
+
+    /// Renders the source line of a diagnostic with its position underlined,
+    /// if the source is attached and contains that position.
+    fn excerpt(&self, diagnostic: &Diagnostic) -> Option<String> {
+        let source_line = self
+            .source
+            .as_deref()?
+            .lines()
+            .nth(diagnostic.line.checked_sub(1)?)?;
+        let width = source_line.chars().count();
+        let start = diagnostic
+            .column
+            .checked_sub(1)
+            .filter(|&start| start <= width)?;
+        // Keep tabs so the underline lines up however wide they are shown.
+        let indent: String = source_line
+            .chars()
+            .take(start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let length = diagnostic
+            .length
+            .unwrap_or(1)
+            .clamp(1, (width - start).max(1));
+        Some(format!(
+            "    {}\n    {}^{}",
+            source_line,
+            indent,
+            "~".repeat(length - 1)
+        ))
     }
 
     /// Returns whether an error has been reported.
     pub fn had_error(&self) -> bool {
         self.had_error
     }
+
+    /// Returns whether a runtime error has been reported, as opposed to only
+    /// errors found before the program ran.
+    pub fn had_runtime_error(&self) -> bool {
+        self.had_runtime_error
+    }
+
+    /// Returns how many errors have been reported, shown or not.
+    pub fn error_count(&self) -> usize {
+        self.error_count
+    }
+
+    /// Returns whether errors beyond the limit have been dropped, after which
+    /// callers may stop early since further errors would not be shown.
+    pub fn limit_reached(&self) -> bool {
+        self.error_count > self.max_errors
+    }
+}
+
+/// Reconstructs approximate source code from tokens, for showing the context
+/// of a diagnostic when the source itself is not at hand.
+///
+/// Lexemes are separated by single spaces, except that closing punctuation
+/// hugs the token before it, opening parentheses and `.` hug the token after
+/// them, and so do prefix operators: the tokens of `print -(a + b);` give back
+/// exactly that text, however they were spaced originally.
+pub fn tokens_to_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    // Whether the previous token ends an operand, which makes a following
+    // `-`, `++` or `--` binary or postfix rather than prefix.
+    let mut after_operand = false;
+    let mut attach_next = true;
+    for token in tokens {
+        let postfix = after_operand && is_inc_dec(&token.token_type);
+        let attaches = attach_next
+            || postfix
+            || matches!(
+                token.token_type,
+                TokenType::RightParen
+                    | TokenType::Semicolon
+                    | TokenType::Comma
+                    | TokenType::Dot
+                    | TokenType::Colon
+            );
+        if !attaches {
+            source.push(' ');
+        }
+        source.push_str(&token.lexeme());
+        attach_next = match &token.token_type {
+            TokenType::LeftParen | TokenType::Dot => true,
+            TokenType::Operator(Operator::Bang) => true,
+            TokenType::Operator(Operator::Minus) => !after_operand,
+            op if is_inc_dec(op) => !postfix,
+            _ => false,
+        };
+        after_operand = postfix
+            || matches!(
+                token.token_type,
+                TokenType::Identifier
+                    | TokenType::Number
+                    | TokenType::String
+                    | TokenType::True
+                    | TokenType::Infinity
+                    | TokenType::NaN
+                    | TokenType::False
+                    | TokenType::Nil
+                    | TokenType::RightParen
+            );
+    }
+    source
+}
+
+fn is_inc_dec(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Operator(Operator::PlusPlus | Operator::MinusMinus)
+    )
 }
@@ -0,0 +1,450 @@
+//! Formats Lox programs back into Lox source code.
+//!
+//! This module contains the `Formatter` struct, which prints a parsed program
+//! as consistently indented source. Unlike the `PrettyPrinter`, expressions
+//! are written infix and only parenthesized where precedence requires it.
+use crate::ast::{
+    DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind, SwitchCase, VarDecl,
+};
+use crate::interner::Symbol;
+use crate::scanner::Comment;
+use crate::token::{precedence, Literal, Operator};
+
+/// Source formatter producing code that parses back to the same program.
+///
+/// Parentheses written in the source are dropped and inserted again only where
+/// an operand binds more loosely than its operator, so `(1 + 2) * 3` keeps
+/// them while `1 + (2 * 3)` becomes `1 + 2 * 3`.
+#[derive(Default)]
+pub struct Formatter {
+    /// Comments of the source, re-emitted above the declarations they precede.
+    comments: Vec<Comment>,
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Formatter { comments: vec![] }
+    }
+
+    /// Keeps the comments the scanner collected from the source, so those on
+    /// the lines directly above a top-level declaration are written above it
+    /// again. Other comments are dropped.
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn format_program(&self, program: &Program) -> String {
+        program
+            .iter()
+            .map(|decl| {
+                self.leading_comments(decl.line)
+                    .into_iter()
+                    .map(|comment| format!("//{}", comment.text.trim_end()))
+                    .chain(std::iter::once(self.format_declaration(decl)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the comments on the unbroken run of lines directly above `line`.
+    fn leading_comments(&self, line: usize) -> Vec<&Comment> {
+        let mut comments = vec![];
+        let mut above = line;
+        while let Some(comment) = above
+            .checked_sub(1)
+            .and_then(|previous| self.comments.iter().find(|c| c.line == previous))
+        {
+            comments.push(comment);
+            above = comment.line;
+        }
+        comments.reverse();
+        comments
+    }
+
+    pub fn format_declaration(&self, decl: &Declaration) -> String {
+        match &decl.kind {
+            DeclKind::VarDecl(var_decl) => {
+                format!(
+                    "{} {};",
+                    var_decl.keyword(),
+                    self.format_var_binding(var_decl)
+                )
+            }
+            DeclKind::MultiVarDecl(var_decls) => {
+                let bindings = var_decls
+                    .iter()
+                    .map(|var_decl| self.format_var_binding(var_decl))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let keyword = var_decls.first().map_or("var", VarDecl::keyword);
+                format!("{} {};", keyword, bindings)
+            }
+            DeclKind::Statement(stmt) => self.format_statement(stmt),
+        }
+    }
+
+    fn format_var_binding(&self, var_decl: &VarDecl) -> String {
+        match &var_decl.initializer {
+            Some(expr) => format!(
+                "{} = {}",
+                var_decl.identifier,
+                self.format_operand(expr, precedence::ASSIGNMENT)
+            ),
+            None => var_decl.identifier.to_string(),
+        }
+    }
+
+    pub fn format_statement(&self, stmt: &Statement) -> String {
+        match &stmt.kind {
+            StmtKind::ExprStmt { expression } => {
+                format!("{};", self.format_expression(expression))
+            }
+            StmtKind::PrintStmt { expressions } => {
+                // The commas separate arguments, so a sequence has to be grouped.
+                let arguments = expressions
+                    .iter()
+                    .map(|expr| self.format_operand(expr, precedence::ASSIGNMENT))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("print {};", arguments)
+            }
+            StmtKind::Block { declarations } => self.format_block(declarations),
+            StmtKind::Break { label } => format!("break{};", format_jump_target(label)),
+            StmtKind::Continue { label } => format!("continue{};", format_jump_target(label)),
+            StmtKind::Switch {
+                discriminant,
+                cases,
+                default,
+            } => self.format_switch(discriminant, cases, default),
+            StmtKind::IfStmt {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => self.format_if_stmt(condition, then_stmt, else_stmt),
+            StmtKind::WhileStmt {
+                condition,
+                do_stmt,
+                label,
+            } => format!(
+                "{}while ({}) {}",
+                format_label(label),
+                self.format_expression(condition),
+                self.format_statement(do_stmt)
+            ),
+            StmtKind::ForStmt {
+                initializer,
+                condition,
+                update,
+                body,
+                label,
+            } => {
+                let init = initializer
+                    .as_ref()
+                    .map(|decl| self.format_declaration(decl))
+                    .unwrap_or_default();
+                let cond = condition
+                    .as_ref()
+                    .map(|expr| format!(" {}", self.format_expression(expr)))
+                    .unwrap_or_default();
+                let upd = update
+                    .as_ref()
+                    .map(|expr| format!(" {}", self.format_expression(expr)))
+                    .unwrap_or_default();
+                format!(
+                    "{}for ({};{};{}) {}",
+                    format_label(label),
+                    init.trim_end_matches(';'),
+                    cond,
+                    upd,
+                    self.format_statement(body)
+                )
+            }
+        }
+    }
+
+    fn format_block(&self, declarations: &[Declaration]) -> String {
+        let inner = declarations
+            .iter()
+            .map(|decl| self.format_declaration(decl))
+            .collect::<Vec<_>>();
+        self.format_braced(&inner)
+    }
+
+    /// Wraps already formatted lines in braces, indenting them by two spaces.
+    ///
+    /// An empty body is written as `{}` on a single line.
+    fn format_braced(&self, inner: &[String]) -> String {
+        if inner.is_empty() {
+            return "{}".to_string();
+        }
+        format!("{{\n{}\n}}", indent(&inner.join("\n")))
+    }
+
+    fn format_switch(
+        &self,
+        discriminant: &Expression,
+        cases: &[SwitchCase],
+        default: &Option<Vec<Declaration>>,
+    ) -> String {
+        let arms = cases
+            .iter()
+            .map(|case| {
+                (
+                    format!("case {}:", self.format_expression(&case.value)),
+                    &case.body,
+                    case.fallthrough,
+                )
+            })
+            .chain(
+                default
+                    .iter()
+                    .map(|body| ("default:".to_string(), body, false)),
+            )
+            .map(|(label, body, fallthrough)| {
+                let statements = body
+                    .iter()
+                    .map(|decl| indent(&self.format_declaration(decl)))
+                    .chain(fallthrough.then(|| indent("fallthrough;")));
+                std::iter::once(label)
+                    .chain(statements)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "switch ({}) {}",
+            self.format_expression(discriminant),
+            self.format_braced(&arms)
+        )
+    }
+
+    /// Formats an `if` statement, keeping `else if` chains on one level.
+    fn format_if_stmt(
+        &self,
+        condition: &Expression,
+        then_stmt: &Statement,
+        else_stmt: &Option<Box<Statement>>,
+    ) -> String {
+        let mut output = format!(
+            "if ({}) {}",
+            self.format_expression(condition),
+            self.format_statement(then_stmt)
+        );
+        let mut else_branch = else_stmt.as_deref();
+        while let Some(stmt) = else_branch {
+            match &stmt.kind {
+                StmtKind::IfStmt {
+                    condition,
+                    then_stmt,
+                    else_stmt,
+                } => {
+                    output.push_str(&format!(
+                        " else if ({}) {}",
+                        self.format_expression(condition),
+                        self.format_statement(then_stmt)
+                    ));
+                    else_branch = else_stmt.as_deref();
+                }
+                _ => {
+                    output.push_str(&format!(" else {}", self.format_statement(stmt)));
+                    else_branch = None;
+                }
+            }
+        }
+        output
+    }
+
+    pub fn format_expression(&self, expr: &Expression) -> String {
+        match &expr.kind {
+            ExprKind::Lit { value } => value.to_string(),
+            ExprKind::Var { identifier } => identifier.to_string(),
+            ExprKind::Grouping { expression } => self.format_expression(expression),
+            ExprKind::BlockExpr { declarations, tail } => {
+                let inner = declarations
+                    .iter()
+                    .map(|decl| self.format_declaration(decl))
+                    .chain(tail.iter().map(|expr| self.format_expression(expr)))
+                    .collect::<Vec<_>>();
+                self.format_braced(&inner)
+            }
+            ExprKind::MapLit { entries } => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            self.format_operand(key, precedence::ASSIGNMENT),
+                            self.format_operand(value, precedence::ASSIGNMENT)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{{}}}", entries)
+            }
+            ExprKind::Index { object, index } => self.format_index(object, index),
+            ExprKind::IndexAssignment {
+                object,
+                index,
+                value,
+            } => format!(
+                "{} = {}",
+                self.format_index(object, index),
+                self.format_operand(value, precedence::ASSIGNMENT)
+            ),
+            ExprKind::Unary { operator, right } => {
+                let operand = self.format_operand(right, precedence::UNARY);
+                // `- -x` must not be written as `--x`, which is a decrement.
+                if *operator == Operator::Minus && operand.starts_with('-') {
+                    format!("{}({})", operator, operand)
+                } else {
+                    format!("{}{}", operator, operand)
+                }
+            }
+            ExprKind::TypeOf { operand } => {
+                format!("typeof {}", self.format_operand(operand, precedence::UNARY))
+            }
+            ExprKind::IncDec {
+                identifier,
+                operator,
+                prefix,
+            } => {
+                if *prefix {
+                    format!("{}{}", operator, identifier)
+                } else {
+                    format!("{}{}", identifier, operator)
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => self.format_infix(
+                left,
+                &operator.to_string(),
+                operator.precedence(),
+                operator.is_right_associative(),
+                right,
+            ),
+            ExprKind::Logical {
+                left,
+                logic_op,
+                right,
+                ..
+            } => self.format_infix(
+                left,
+                &logic_op.to_string(),
+                logic_op.precedence().unwrap_or(precedence::LOGICAL),
+                logic_op.is_right_associative(),
+                right,
+            ),
+            ExprKind::Assignment { identifier, value } => format!(
+                "{} = {}",
+                identifier,
+                self.format_operand(value, precedence::ASSIGNMENT)
+            ),
+            ExprKind::GlobalAssignment { identifier, value } => format!(
+                "global {} = {}",
+                identifier,
+                self.format_operand(value, precedence::ASSIGNMENT)
+            ),
+            ExprKind::Sequence { expressions } => expressions
+                .iter()
+                .map(|expr| self.format_operand(expr, precedence::ASSIGNMENT))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    fn format_index(&self, object: &Expression, index: &Expression) -> String {
+        format!(
+            "{}[{}]",
+            self.format_operand(object, precedence::POSTFIX),
+            self.format_expression(index)
+        )
+    }
+
+    /// Formats a binary or logical expression.
+    ///
+    /// An operand on the side the operator groups towards may share its
+    /// precedence; on the other side it must bind tighter, so `1 - (2 - 3)`
+    /// and `(2 ** 3) ** 2` keep their parentheses.
+    fn format_infix(
+        &self,
+        left: &Expression,
+        operator: &str,
+        precedence: u8,
+        right_associative: bool,
+        right: &Expression,
+    ) -> String {
+        let (left_min, right_min) = if right_associative {
+            (precedence + 1, precedence)
+        } else {
+            (precedence, precedence + 1)
+        };
+        format!(
+            "{} {} {}",
+            self.format_operand(left, left_min),
+            operator,
+            self.format_operand(right, right_min)
+        )
+    }
+
+    /// Formats an operand, parenthesizing it if it binds more loosely than
+    /// `min_precedence`.
+    fn format_operand(&self, expr: &Expression, min_precedence: u8) -> String {
+        let formatted = self.format_expression(expr);
+        if expression_precedence(expr) < min_precedence {
+            format!("({})", formatted)
+        } else {
+            formatted
+        }
+    }
+}
+
+/// Returns the precedence of the operator at the root of an expression, with
+/// groupings looked through and atoms binding tightest.
+fn expression_precedence(expr: &Expression) -> u8 {
+    match &expr.kind {
+        ExprKind::Grouping { expression } => expression_precedence(expression),
+        // A negative number, as left behind by constant folding, reads as a negation.
+        ExprKind::Lit {
+            value: Literal::Number(n),
+        } if n.is_sign_negative() => precedence::UNARY,
+        ExprKind::Lit { .. }
+        | ExprKind::Var { .. }
+        | ExprKind::BlockExpr { .. }
+        | ExprKind::MapLit { .. } => precedence::PRIMARY,
+        ExprKind::Index { .. } => precedence::POSTFIX,
+        ExprKind::Unary { .. } | ExprKind::TypeOf { .. } => precedence::UNARY,
+        ExprKind::IncDec { prefix: true, .. } => precedence::UNARY,
+        ExprKind::IncDec { prefix: false, .. } => precedence::POSTFIX,
+        ExprKind::Binary { operator, .. } => operator.precedence(),
+        ExprKind::Logical { logic_op, .. } => logic_op.precedence().unwrap_or(precedence::LOGICAL),
+        ExprKind::Assignment { .. }
+        | ExprKind::GlobalAssignment { .. }
+        | ExprKind::IndexAssignment { .. } => precedence::ASSIGNMENT,
+        ExprKind::Sequence { .. } => precedence::SEQUENCE,
+    }
+}
+
+/// Formats the `label: ` prefix of a labeled loop, or nothing.
+fn format_label(label: &Option<Symbol>) -> String {
+    label.map(|l| format!("{}: ", l)).unwrap_or_default()
+}
+
+/// Formats the ` label` following `break` or `continue`, or nothing.
+fn format_jump_target(label: &Option<Symbol>) -> String {
+    label.map(|l| format!(" {}", l)).unwrap_or_default()
+}
+
+/// Indents every line of `text` by two spaces.
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,80 @@
+//! Provides string interning for identifiers.
+//!
+//! This module contains the `Interner` struct, which maps every distinct
+//! identifier to a small `Symbol`, so identifiers can be copied, compared and
+//! hashed without touching the underlying text. Only identifiers and keywords
+//! are interned; literals and other lexemes keep their own text, so the
+//! interner grows with the number of distinct names rather than with the size
+//! of the program.
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter, Result},
+    marker::PhantomData,
+};
+
+/// An interned identifier, identified by its index in the interner.
+///
+/// Symbols are only meaningful on the thread that created them, since each
+/// thread owns its own interner, so they cannot be sent to another thread.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+impl Symbol {
+    /// Interns `text`, returning the existing symbol if it was seen before.
+    pub fn intern(text: &str) -> Self {
+        INTERNER.with(|interner| interner.borrow_mut().intern(text))
+    }
+
+    /// Calls `f` with the text this symbol was interned from.
+    pub fn with_str<R>(self, f: impl FnOnce(&str) -> R) -> R {
+        INTERNER.with(|interner| f(interner.borrow().resolve(self)))
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        self.with_str(|text| write!(f, "{}", text))
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        self.with_str(|text| write!(f, "Symbol({:?})", text))
+    }
+}
+
+/// Two-way mapping between strings and symbols.
+///
+/// The interner owns the text of every symbol, which lives as long as the
+/// interner; interning only identifiers keeps that to the names a program uses.
+#[derive(Default)]
+pub struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    strings: Vec<Box<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let index = u32::try_from(self.strings.len()).expect("Interner ran out of symbols");
+        let symbol = Symbol(index, PhantomData);
+        self.strings.push(text.into());
+        self.symbols.insert(text.into(), symbol);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
@@ -2,19 +2,105 @@
 //!
 //! This module is responsible for evaluating an expression to a value.
 
-use crate::ast::{DeclKind, Declaration, ExprKind, Expression, Statement, StmtKind, VarDecl};
-use crate::environment::Environment;
+use crate::ast::{
+    DeclKind, Declaration, ExprKind, Expression, Statement, StmtKind, SwitchCase, VarDecl,
+};
+use crate::environment::{Environment, EnvironmentError};
 use crate::error_reporter::{ErrorReporter, RuntimeError};
-use crate::token::{Literal, Operator, TokenType};
+use crate::interner::Symbol;
+use crate::token::{format_number, Literal, MapKey, Operator, TokenType};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
 /// Represents a value to clarify difference between literal input and value output.
 pub type Value = Literal;
 
+thread_local! {
+    /// Position of the statement last executed on this thread, so an
+    /// internal error can point at the code that was running.
+    static LAST_POSITION: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// Returns the line and column of the statement or declaration the
+/// interpreter on this thread executed last, if it executed any.
+pub fn last_position() -> Option<(usize, usize)> {
+    LAST_POSITION.with(Cell::get)
+}
+
+/// Records `line` and `column` as the position of the code being executed.
+fn set_last_position(line: usize, column: usize) {
+    LAST_POSITION.with(|position| position.set(Some((line, column))));
+}
+
+/// How control leaves a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    /// Continue with the next statement.
+    Normal,
+    /// Leave the innermost enclosing loop, or the loop with the given label.
+    Break(Option<Symbol>),
+    /// Skip to the next iteration of the innermost enclosing loop, or of the
+    /// loop with the given label.
+    Continue(Option<Symbol>),
+}
+
+/// What a loop does after its body ended with a given `Flow`.
+enum LoopStep {
+    /// Go on with the next iteration.
+    Next,
+    /// Stop looping and carry on after the loop.
+    Exit,
+    /// Stop looping and hand the flow on to an outer loop it targets.
+    Propagate(Flow),
+}
+
+impl Flow {
+    /// Decides how the loop labeled `label` handles this flow of its body.
+    ///
+    /// Unlabeled jumps target the innermost loop; labeled ones pass through
+    /// loops with other labels until they reach their own.
+    fn in_loop(self, label: Option<Symbol>) -> LoopStep {
+        match self {
+            Flow::Normal => LoopStep::Next,
+            Flow::Break(target) if target.is_none() || target == label => LoopStep::Exit,
+            Flow::Continue(target) if target.is_none() || target == label => LoopStep::Next,
+            flow => LoopStep::Propagate(flow),
+        }
+    }
+}
+
 /// The Lox Interpreter
 pub struct Interpreter {
     /// Handles reporting of runtime errors
     pub error_reporter: ErrorReporter,
     pub environment_stack: Environment,
+    /// Whether reading an uninitialized variable aborts the program.
+    strict: bool,
+    /// How many expression evaluations are currently nested.
+    depth: usize,
+    /// The nesting depth at which evaluation aborts with a stack overflow.
+    max_depth: usize,
+    /// Number of decimal places `print` rounds numbers to, or `None` for full precision.
+    float_precision: Option<usize>,
+    /// Number of iterations a single loop may run, or `None` for no limit.
+    loop_limit: Option<usize>,
+    /// Whether to print a trace of the evaluation to stderr.
+    trace: bool,
+}
+
+/// The default for `Interpreter::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// The longest string, in bytes, that repeating a string may produce.
+pub const MAX_STRING_LENGTH: usize = 1 << 30;
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
@@ -23,70 +109,237 @@ impl Interpreter {
         Interpreter {
             error_reporter: ErrorReporter::new(),
             environment_stack: Environment::new(),
+            strict: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            float_precision: None,
+            loop_limit: None,
+            trace: false,
         }
     }
 
-    pub fn evaluate_program(&mut self, program: &Vec<Declaration>) {
+    /// Enables or disables strict mode.
+    ///
+    /// In strict mode reading a declared but uninitialized variable is a fatal
+    /// runtime error instead of reporting and evaluating to `nil`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets how deeply expression evaluation may nest before it is aborted
+    /// with a stack overflow error, instead of overflowing the native stack.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Makes `print` round numbers to `precision` decimal places, dropping
+    /// trailing zeros, so `0.1 + 0.2` prints as `0.3` with a precision of 2.
+    ///
+    /// `None`, the default, prints numbers at full precision.
+    pub fn with_float_precision(mut self, precision: Option<usize>) -> Self {
+        self.float_precision = precision;
+        self
+    }
+
+    /// Aborts the program with a runtime error once any single loop runs
+    /// more than `limit` iterations, guarding against accidental infinite loops.
+    ///
+    /// `None`, the default, lets loops run forever.
+    pub fn with_loop_limit(mut self, limit: Option<usize>) -> Self {
+        self.loop_limit = limit;
+        self
+    }
+
+    /// Prints a trace of the evaluation to stderr, for seeing how the tree is
+    /// walked.
+    ///
+    /// Every statement is listed as it is executed, e.g. `exec Print`, and
+    /// every expression once it produced its value, e.g. `eval Binary + -> 3`,
+    /// so operands come before their operator. Lines are indented by how
+    /// deeply evaluation is nested.
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Evaluates every declaration of the program in order.
+    ///
+    /// Execution stops at the first fatal runtime error, which has already
+    /// been reported through the error reporter and is returned.
+    pub fn evaluate_program(&mut self, program: &Vec<Declaration>) -> Result<(), RuntimeError> {
         for declaration in program {
-            self.evaluate_declaration(declaration)
+            // The parser rejects `break` and `continue` outside of loops, so
+            // any flow other than normal can only end the declaration.
+            self.evaluate_declaration(declaration)?;
         }
+        Ok(())
     }
 
-    fn evaluate_declaration(&mut self, declaration: &Declaration) {
+    /// Evaluates a single expression in the current environment.
+    ///
+    /// Errors are reported through the error reporter as usual, but unlike
+    /// during a program run, any error makes this return `Err`: a fatal one
+    /// as its own variant, and a non-fatal one, such as reading an
+    /// uninitialized variable outside strict mode, as `RuntimeError::Reported`.
+    pub fn eval_expression(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        let errors_before = self.error_reporter.error_count();
+        let value = self.evaluate_expression(expression)?;
+        if self.error_reporter.error_count() > errors_before {
+            return Err(RuntimeError::Reported);
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a program typed at the REPL and returns the value to echo.
+    ///
+    /// That is the value of the last declaration if it is an expression
+    /// statement, so entering `1 + 2;` echoes `3`. Other declarations, and
+    /// programs stopped by a fatal error, have nothing to echo.
+    pub fn evaluate_repl_program(&mut self, program: &[Declaration]) -> Option<Value> {
+        let (last, rest) = program.split_last()?;
+        for declaration in rest {
+            self.evaluate_declaration(declaration).ok()?;
+        }
+        match &last.kind {
+            DeclKind::Statement(Statement {
+                kind: StmtKind::ExprStmt { expression },
+                ..
+            }) => self.evaluate_expression(expression).ok(),
+            _ => {
+                let _ = self.evaluate_declaration(last);
+                None
+            }
+        }
+    }
+
+    fn evaluate_declaration(&mut self, declaration: &Declaration) -> Result<Flow, RuntimeError> {
+        set_last_position(declaration.line, declaration.column);
         match &declaration.kind {
-            DeclKind::VarDecl(var_decl) => self.evaluate_var_decl(var_decl),
-            DeclKind::Statement(statement) => self.evaluate_statement(statement),
+            DeclKind::VarDecl(var_decl) => self.evaluate_var_decl(var_decl)?,
+            DeclKind::MultiVarDecl(var_decls) => var_decls
+                .iter()
+                .try_for_each(|var_decl| self.evaluate_var_decl(var_decl))?,
+            DeclKind::Statement(statement) => return self.evaluate_statement(statement),
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Evaluates declarations in order, stopping early at a `break` or
+    /// `continue` and passing it on.
+    fn evaluate_declarations(
+        &mut self,
+        declarations: &[Declaration],
+    ) -> Result<Flow, RuntimeError> {
+        for declaration in declarations {
+            let flow = self.evaluate_declaration(declaration)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
         }
+        Ok(Flow::Normal)
     }
 
-    fn evaluate_var_decl(&mut self, var_decl: &VarDecl) {
+    fn evaluate_var_decl(&mut self, var_decl: &VarDecl) -> Result<(), RuntimeError> {
         let value = match &var_decl.initializer {
-            Some(expression) => Some(self.evaluate_expression(expression)),
+            Some(expression) => Some(self.evaluate_expression(expression)?),
             None => None,
         };
-        self.environment_stack
-            .define(var_decl.identifier.clone(), value);
+        match value {
+            Some(value) if var_decl.constant => {
+                self.environment_stack
+                    .define_constant(var_decl.identifier, value);
+            }
+            value => {
+                self.environment_stack.define(var_decl.identifier, value);
+            }
+        }
+        Ok(())
     }
 
-    fn evaluate_statement(&mut self, statement: &Statement) {
+    fn evaluate_statement(&mut self, statement: &Statement) -> Result<Flow, RuntimeError> {
+        set_last_position(statement.line, statement.column);
+        if self.trace {
+            eprintln!(
+                "{}exec {}",
+                "  ".repeat(self.depth),
+                statement_label(&statement.kind)
+            );
+        }
         match &statement.kind {
-            StmtKind::PrintStmt { expression } => {
-                println!("{}", self.evaluate_expression(expression))
+            StmtKind::PrintStmt { expressions } => {
+                let mut printed = Vec::with_capacity(expressions.len());
+                for expression in expressions {
+                    let value = self.evaluate_expression(expression)?;
+                    printed.push(self.display_value(&value));
+                }
+                println!("{}", printed.join(" "))
             }
 
             StmtKind::ExprStmt { expression } => {
-                let _ = self.evaluate_expression(expression);
+                self.evaluate_expression(expression)?;
             }
             StmtKind::IfStmt {
                 condition,
                 then_stmt,
                 else_stmt,
             } => {
-                let condition_value = self.evaluate_expression(condition);
+                let condition_value = self.evaluate_expression(condition)?;
                 if self.is_truthy(&condition_value) {
-                    self.evaluate_statement(then_stmt)
+                    return self.evaluate_statement(then_stmt);
                 } else if let Some(stmt) = else_stmt {
-                    self.evaluate_statement(stmt)
+                    return self.evaluate_statement(stmt);
                 }
             }
-            StmtKind::WhileStmt { condition, do_stmt } => {
-                let mut condition_value = self.evaluate_expression(condition);
-                while self.is_truthy(&condition_value) {
-                    self.evaluate_statement(do_stmt);
-                    condition_value = self.evaluate_expression(condition);
-                }
+            StmtKind::WhileStmt {
+                condition,
+                do_stmt,
+                label,
+            } => {
+                return self.with_scope(statement.line, statement.column, |interpreter| {
+                    let mut condition_value = interpreter.evaluate_expression(condition)?;
+                    let mut iterations = 0;
+                    while interpreter.is_truthy(&condition_value) {
+                        iterations += 1;
+                        interpreter.check_loop_limit(
+                            iterations,
+                            statement.line,
+                            statement.column,
+                        )?;
+                        match interpreter.evaluate_loop_body(do_stmt)?.in_loop(*label) {
+                            LoopStep::Next => {}
+                            LoopStep::Exit => break,
+                            LoopStep::Propagate(flow) => return Ok(flow),
+                        }
+                        condition_value = interpreter.evaluate_expression(condition)?;
+                    }
+                    Ok(Flow::Normal)
+                })
             }
             StmtKind::Block { declarations } => {
-                self.environment_stack.increase_scope();
-                for declaration in declarations {
-                    self.evaluate_declaration(declaration);
+                return self.evaluate_block(declarations, statement.line, statement.column)
+            }
+            StmtKind::Switch {
+                discriminant,
+                cases,
+                default,
+            } => {
+                let value = self.evaluate_expression(discriminant)?;
+                let mut matched = None;
+                for (index, case) in cases.iter().enumerate() {
+                    if values_equal(&self.evaluate_expression(&case.value)?, &value) {
+                        matched = Some(index);
+                        break;
+                    }
                 }
-                if let Err(_) = self.environment_stack.reduce_scope() {
-                    self.error_reporter.error(
-                        statement.line,
-                        statement.column,
-                        "Trying to reduce scope but already at global",
-                    );
+                for body in switch_arms(cases, default, matched) {
+                    match self.evaluate_block(body, statement.line, statement.column)? {
+                        Flow::Normal => {}
+                        // An unlabeled break leaves the switch; labeled ones target loops.
+                        Flow::Break(None) => break,
+                        flow => return Ok(flow),
+                    }
                 }
             }
             StmtKind::ForStmt {
@@ -94,95 +347,370 @@ impl Interpreter {
                 condition,
                 update,
                 body,
-            } => self.evaluate_for_statement(
-                initializer,
-                condition,
-                update,
-                body,
-                statement.line,
-                statement.column,
-            ),
+                label,
+            } => {
+                let (line, column) = (statement.line, statement.column);
+                return self.with_scope(line, column, |interpreter| {
+                    if let Some(init) = initializer {
+                        interpreter.evaluate_declaration(init)?;
+                    }
+                    interpreter.with_scope(line, column, |interpreter| {
+                        interpreter.run_for_loop(condition, update, body, *label, line, column)
+                    })
+                });
+            }
+            StmtKind::Break { label } => return Ok(Flow::Break(*label)),
+            StmtKind::Continue { label } => return Ok(Flow::Continue(*label)),
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Runs `run` in a fresh scope that is discarded afterwards, even when a
+    /// fatal error unwinds through it.
+    fn with_scope<T, F>(&mut self, line: usize, column: usize, run: F) -> Result<T, RuntimeError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RuntimeError>,
+    {
+        self.environment_stack.increase_scope();
+        let result = run(self);
+        if self.environment_stack.reduce_scope().is_err() {
+            self.error_reporter
+                .error(line, column, "Trying to reduce scope but already at global");
         }
+        result
     }
 
-    fn evaluate_for_statement(
+    /// Runs declarations in a fresh scope that is discarded afterwards.
+    fn evaluate_block(
+        &mut self,
+        declarations: &[Declaration],
+        line: usize,
+        column: usize,
+    ) -> Result<Flow, RuntimeError> {
+        self.with_scope(line, column, |interpreter| {
+            interpreter.evaluate_declarations(declarations)
+        })
+    }
+
+    /// Runs one iteration of a loop body in the scope the loop pushed for it.
+    ///
+    /// A block body declares its variables straight into that scope, which is
+    /// cleared after every iteration instead of being replaced by a new one,
+    /// so tight loops don't allocate a map per iteration.
+    fn evaluate_loop_body(&mut self, body: &Statement) -> Result<Flow, RuntimeError> {
+        let result = match &body.kind {
+            StmtKind::Block { declarations } => self.evaluate_declarations(declarations),
+            _ => self.evaluate_statement(body),
+        };
+        self.environment_stack.clear_scope();
+        result
+    }
+
+    /// Runs a `for` loop itself, with the body's reusable scope on top of the
+    /// scope holding the loop variable.
+    ///
+    /// A missing condition loops until a `break`. The update also runs after
+    /// a `continue`, so `continue` still advances the loop.
+    fn run_for_loop(
         &mut self,
-        initializer: &Option<Box<Declaration>>,
         condition: &Option<Box<Expression>>,
         update: &Option<Box<Expression>>,
-        body: &Box<Statement>,
+        body: &Statement,
+        label: Option<Symbol>,
         line: usize,
         column: usize,
-    ) {
-        self.environment_stack.increase_scope();
-        if let Some(init) = initializer {
-            self.evaluate_declaration(init);
-        }
+    ) -> Result<Flow, RuntimeError> {
+        let mut iterations = 0;
         loop {
             if let Some(cond) = condition {
-                let cond_value = &self.evaluate_expression(cond);
-                if !self.is_truthy(&cond_value) {
+                let cond_value = &self.evaluate_expression(cond)?;
+                if !self.is_truthy(cond_value) {
                     break;
                 };
+            }
+            iterations += 1;
+            self.check_loop_limit(iterations, line, column)?;
 
-                self.evaluate_statement(body);
+            match self.evaluate_loop_body(body)?.in_loop(label) {
+                LoopStep::Next => {}
+                LoopStep::Exit => break,
+                LoopStep::Propagate(flow) => return Ok(flow),
+            }
 
-                if let Some(upd) = update {
-                    self.evaluate_expression(upd);
-                }
+            if let Some(upd) = update {
+                self.evaluate_expression(upd)?;
             }
         }
-        if let Err(_) = self.environment_stack.reduce_scope() {
-            self.error_reporter
-                .error(line, column, "Trying to reduce scope but already at global");
+        Ok(Flow::Normal)
+    }
+
+    /// Fails with a reported error once a loop at `line` and `column` is
+    /// about to start an iteration beyond the configured loop limit.
+    fn check_loop_limit(
+        &mut self,
+        iterations: usize,
+        line: usize,
+        column: usize,
+    ) -> Result<(), RuntimeError> {
+        match self.loop_limit {
+            Some(limit) if iterations > limit => self.fail(RuntimeError::LoopLimitExceeded {
+                line,
+                column,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports a fatal runtime error and returns it, to be propagated with `?`.
+    fn fail<T>(&mut self, error: RuntimeError) -> Result<T, RuntimeError> {
+        self.error_reporter.runtime_error(&error);
+        Err(error)
+    }
+
+    /// Builds a `TypeMismatch` error underlining `operator`.
+    fn type_mismatch(
+        operator: &Operator,
+        line: usize,
+        column: usize,
+        message: String,
+    ) -> RuntimeError {
+        RuntimeError::TypeMismatch {
+            line,
+            column,
+            length: operator_width(operator),
+            message,
         }
     }
+
     /// Evaluates an entire expression and returns a Value
-    fn evaluate_expression(&mut self, expression: &Expression) -> Value {
+    ///
+    /// An `Err` means a fatal runtime error that has already been reported
+    /// and should abort the program.
+    pub(crate) fn evaluate_expression(
+        &mut self,
+        expression: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        if self.depth >= self.max_depth {
+            return self.fail(RuntimeError::StackOverflow {
+                line: expression.line,
+                column: expression.column,
+                max_depth: self.max_depth,
+            });
+        }
+        self.depth += 1;
+        let result = self.evaluate_expression_kind(expression);
+        self.depth -= 1;
+        if self.trace {
+            let outcome = match &result {
+                Ok(value) => value.repl_display(),
+                Err(_) => "error".to_string(),
+            };
+            eprintln!(
+                "{}eval {} -> {}",
+                "  ".repeat(self.depth),
+                trace_label(&expression.kind),
+                outcome
+            );
+        }
+        result
+    }
+
+    fn evaluate_expression_kind(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
         match &expression.kind {
-            ExprKind::Lit { value } => value.clone(),
+            ExprKind::Lit { value } => Ok(value.clone()),
             ExprKind::Var { identifier } => {
-                self.evaluate_var(identifier, expression.line, expression.column)
+                self.evaluate_var(*identifier, expression.line, expression.column)
             }
             ExprKind::Grouping { expression } => self.evaluate_expression(expression),
+            ExprKind::BlockExpr { declarations, tail } => self.evaluate_block_expression(
+                declarations,
+                tail,
+                expression.line,
+                expression.column,
+            ),
+            ExprKind::MapLit { entries } => self.evaluate_map_literal(entries),
+            ExprKind::Index { object, index } => self.evaluate_index(object, index),
+            ExprKind::IndexAssignment {
+                object,
+                index,
+                value,
+            } => self.evaluate_index_assignment(object, index, value),
             ExprKind::Unary { operator, right } => {
                 self.evaluate_unary(operator, right, expression.line, expression.column)
             }
+            ExprKind::TypeOf { operand } => {
+                let value = self.evaluate_expression(operand)?;
+                Ok(Value::String(value.type_name().into()))
+            }
+            ExprKind::IncDec {
+                identifier,
+                operator,
+                prefix,
+            } => self.evaluate_inc_dec(
+                *identifier,
+                operator,
+                *prefix,
+                expression.line,
+                expression.column,
+            ),
             ExprKind::Binary {
                 left,
                 operator,
+                op_line,
+                op_column,
                 right,
-            } => self.evaluate_binary(left, operator, right, expression.line, expression.column),
+            } => self.evaluate_binary(left, operator, right, *op_line, *op_column),
             ExprKind::Logical {
                 left,
                 logic_op,
                 right,
+                ..
             } => self.evaluate_logical(left, logic_op, right),
             ExprKind::Assignment { identifier, value } => {
-                self.evaluate_assignment(identifier, value)
+                self.evaluate_assignment(*identifier, value, expression.line, expression.column)
+            }
+            ExprKind::GlobalAssignment { identifier, value } => self.evaluate_global_assignment(
+                *identifier,
+                value,
+                expression.line,
+                expression.column,
+            ),
+            ExprKind::Sequence { expressions } => {
+                let mut value = Value::Nil;
+                for expression in expressions {
+                    value = self.evaluate_expression(expression)?;
+                }
+                Ok(value)
             }
         }
     }
 
-    fn evaluate_var(&mut self, identifier: &str, line: usize, column: usize) -> Value {
-        match self.environment_stack.get(identifier) {
-            Ok(value) => value,
-            Err(RuntimeError::UnInitializedVariable) => {
-                self.error_reporter.error(
-                    line,
-                    column,
-                    &format!("Uninitialized Variable: {}", identifier),
-                );
-                Value::Nil
+    /// Runs a block expression's declarations in a fresh scope and returns the
+    /// value of its trailing expression, or nil if it has none.
+    fn evaluate_block_expression(
+        &mut self,
+        declarations: &[Declaration],
+        tail: &Option<Box<Expression>>,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        self.with_scope(line, column, |interpreter| {
+            if interpreter.evaluate_declarations(declarations)? != Flow::Normal {
+                return interpreter.fail(RuntimeError::InvalidControlFlow { line, column });
+            }
+            match tail {
+                Some(expression) => interpreter.evaluate_expression(expression),
+                None => Ok(Value::Nil),
             }
-            Err(_) => {
-                self.error_reporter.error(
+        })
+    }
+
+    /// Builds a new map from the entries of a map literal, evaluated in order.
+    ///
+    /// A later entry with the same key replaces an earlier one.
+    fn evaluate_map_literal(
+        &mut self,
+        entries: &[(Expression, Expression)],
+    ) -> Result<Value, RuntimeError> {
+        let mut map = HashMap::new();
+        for (key, value) in entries {
+            let key_value = self.evaluate_expression(key)?;
+            let value = self.evaluate_expression(value)?;
+            map.insert(self.map_key(key_value, key)?, value);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    /// Reads `object[index]`. A key missing from the map reads as nil.
+    fn evaluate_index(
+        &mut self,
+        object: &Expression,
+        index: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        let object_value = self.evaluate_expression(object)?;
+        let index_value = self.evaluate_expression(index)?;
+        let map = self.indexed_map(object_value, object)?;
+        let key = self.map_key(index_value, index)?;
+        let value = map.borrow().get(&key).cloned();
+        Ok(value.unwrap_or(Value::Nil))
+    }
+
+    /// Writes `object[index] = value`, adding the key if the map lacks it, and
+    /// yields the assigned value.
+    fn evaluate_index_assignment(
+        &mut self,
+        object: &Expression,
+        index: &Expression,
+        value: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        let object_value = self.evaluate_expression(object)?;
+        let index_value = self.evaluate_expression(index)?;
+        let value = self.evaluate_expression(value)?;
+        let map = self.indexed_map(object_value, object)?;
+        let key = self.map_key(index_value, index)?;
+        map.borrow_mut().insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Returns the map being indexed, failing at `object` if the value is
+    /// not a map.
+    fn indexed_map(
+        &mut self,
+        value: Value,
+        object: &Expression,
+    ) -> Result<Rc<RefCell<HashMap<MapKey, Value>>>, RuntimeError> {
+        match value {
+            Value::Map(map) => Ok(map),
+            other => self.fail(RuntimeError::TypeMismatch {
+                line: object.line,
+                column: object.column,
+                length: 1,
+                message: format!("Only maps can be indexed, not {}.", other.type_name()),
+            }),
+        }
+    }
+
+    /// Returns the key a value indexes a map by, failing at `key` if the value
+    /// is a map.
+    fn map_key(&mut self, value: Value, key: &Expression) -> Result<MapKey, RuntimeError> {
+        match MapKey::from_literal(&value) {
+            Some(map_key) => Ok(map_key),
+            None => self.fail(RuntimeError::TypeMismatch {
+                line: key.line,
+                column: key.column,
+                length: 1,
+                message: "A map cannot be used as a map key.".to_string(),
+            }),
+        }
+    }
+
+    fn evaluate_var(
+        &mut self,
+        identifier: Symbol,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        match self.environment_stack.get(identifier) {
+            Ok(value) => Ok(value),
+            Err(EnvironmentError::UnInitializedVariable) => {
+                let error = RuntimeError::UnInitializedVariable {
+                    name: identifier,
                     line,
                     column,
-                    &format!("Undefined Variable: {}", identifier),
-                );
-                Value::Nil
+                };
+                if self.strict {
+                    return self.fail(error);
+                }
+                // Outside strict mode the read is reported but yields nil.
+                self.error_reporter.runtime_error(&error);
+                Ok(Value::Nil)
             }
+            Err(_) => self.fail(RuntimeError::UndefinedVariable {
+                name: identifier,
+                line,
+                column,
+            }),
         }
     }
 
@@ -193,30 +721,65 @@ impl Interpreter {
         right: &Expression,
         line: usize,
         column: usize,
-    ) -> Value {
-        let right_val = self.evaluate_expression(right);
+    ) -> Result<Value, RuntimeError> {
+        let right_val = self.evaluate_expression(right)?;
         match operator {
-            Operator::Bang => Value::Boolean(!self.is_truthy(&right_val)),
+            Operator::Bang => Ok(Value::Boolean(!self.is_truthy(&right_val))),
             Operator::Minus => match right_val {
-                Value::Number(n) => Value::Number(-n),
-                _ => {
-                    self.error_reporter.error(
-                        line,
-                        column,
-                        &format!("{}, is not a number", right_val),
-                    );
-                    Value::Nil
-                }
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => self.fail(Self::type_mismatch(
+                    operator,
+                    line,
+                    column,
+                    format!("{}, is not a number", right_val),
+                )),
             },
-            _ => {
-                self.error_reporter.error(
+            _ => self.fail(Self::type_mismatch(
+                operator,
+                line,
+                column,
+                format!("Using {} as unary operator not allowed.", operator),
+            )),
+        }
+    }
+
+    /// Evaluates `++`/`--`, writing the new value back to the variable.
+    ///
+    /// Prefix forms evaluate to the updated value, postfix forms to the original one.
+    fn evaluate_inc_dec(
+        &mut self,
+        identifier: Symbol,
+        operator: &Operator,
+        prefix: bool,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        let old_value = match self.environment_stack.get(identifier) {
+            Ok(Value::Number(n)) => n,
+            // Let the variable lookup report undefined and uninitialized variables.
+            Err(_) => return self.evaluate_var(identifier, line, column),
+            Ok(other) => {
+                return self.fail(Self::type_mismatch(
+                    operator,
                     line,
                     column,
-                    &format!("Using {} as unary operator not allowed.", operator),
-                );
-                Value::Nil
+                    format!("Cannot apply {} to {}, it is not a number", operator, other),
+                ))
             }
+        };
+        let new_value = match operator {
+            Operator::PlusPlus => old_value + 1.0,
+            Operator::MinusMinus => old_value - 1.0,
+            _ => unreachable!("Operator is not an increment or decrement"),
+        };
+        // The variable was just read, so it exists, but it may be a constant.
+        if let Err(error) = self
+            .environment_stack
+            .assign(identifier, Value::Number(new_value))
+        {
+            return self.fail(Self::assignment_error(error, identifier, line, column));
         }
+        Ok(Value::Number(if prefix { new_value } else { old_value }))
     }
 
     fn evaluate_binary(
@@ -226,27 +789,32 @@ impl Interpreter {
         right: &Expression,
         line: usize,
         column: usize,
-    ) -> Value {
-        let left_val = self.evaluate_expression(left);
-        let right_val = self.evaluate_expression(right);
+    ) -> Result<Value, RuntimeError> {
+        let left_val = self.evaluate_expression(left)?;
+        let right_val = self.evaluate_expression(right)?;
         match operator {
-            Operator::Minus | Operator::Plus | Operator::Star | Operator::Slash => {
+            Operator::Minus
+            | Operator::Plus
+            | Operator::Star
+            | Operator::Slash
+            | Operator::StarStar => {
                 self.evaluate_arithmetic(left_val, operator, right_val, line, column)
             }
             Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual => {
                 self.evaluate_comparator(left_val, operator, right_val, line, column)
             }
             Operator::EqualEqual | Operator::BangEqual => {
-                self.evaluate_equals(left_val, operator, right_val)
+                Ok(self.evaluate_equals(left_val, operator, right_val))
             }
-            _ => {
-                self.error_reporter.error(
-                    line,
-                    column,
-                    &format!("Using {} as a binary operator is not allowed", operator),
-                );
-                Value::Nil
+            Operator::Amp | Operator::Pipe | Operator::Caret | Operator::Shl | Operator::Shr => {
+                self.evaluate_bitwise(left_val, operator, right_val, line, column)
             }
+            _ => self.fail(Self::type_mismatch(
+                operator,
+                line,
+                column,
+                format!("Using {} as a binary operator is not allowed", operator),
+            )),
         }
     }
 
@@ -257,53 +825,165 @@ impl Interpreter {
         right_val: Value,
         line: usize,
         column: usize,
-    ) -> Value {
+    ) -> Result<Value, RuntimeError> {
         match (left_val, right_val) {
-            (Value::Number(l), Value::Number(r)) => match operator {
-                Operator::Minus => Value::Number(l - r),
-                Operator::Plus => Value::Number(l + r),
-                Operator::Slash => Value::Number(l / r),
-                Operator::Star => Value::Number(l * r),
-                _ => unreachable!("Operator is not part of arithmetic"),
-            },
-            (Value::String(l), Value::String(r)) => match operator {
-                Operator::Plus => Value::String(format!("{}{}", l, r)),
-                _ => {
-                    self.error_reporter.error(
+            (Value::Number(_), Value::Number(r)) if *operator == Operator::Slash && r == 0.0 => {
+                self.fail(RuntimeError::DivisionByZero { line, column })
+            }
+            (Value::Number(l), Value::Number(r)) => {
+                let result = match operator {
+                    Operator::Minus => l - r,
+                    Operator::Plus => l + r,
+                    Operator::Slash => l / r,
+                    Operator::Star => l * r,
+                    Operator::StarStar => l.powf(r),
+                    _ => unreachable!("Operator is not part of arithmetic"),
+                };
+                if result.is_infinite() && l.is_finite() && r.is_finite() {
+                    return self.fail(RuntimeError::Overflow {
                         line,
                         column,
-                        &format!(
-                            "Using {} on strings [{}, {}] is not allowed",
-                            operator, l, r
+                        length: operator_width(operator),
+                        message: format!(
+                            "Arithmetic overflow: {} {} {} is too large to represent",
+                            format_number(l),
+                            operator,
+                            format_number(r)
                         ),
-                    );
-                    Value::Nil
+                    });
                 }
+                Ok(Value::Number(result))
+            }
+            (Value::String(l), Value::String(r)) => match operator {
+                Operator::Plus => Ok(Value::String(format!("{}{}", l, r).into())),
+                _ => self.fail(Self::type_mismatch(
+                    operator,
+                    line,
+                    column,
+                    format!(
+                        "Using {} on strings [{}, {}] is not allowed",
+                        operator, l, r
+                    ),
+                )),
             },
             (Value::String(l), r) | (r, Value::String(l)) => match operator {
-                Operator::Plus => Value::String(format!("{}{}", l, r)),
-                _ => {
-                    self.error_reporter.error(
-                        line,
-                        column,
-                        &format!(
-                            "Using {} with string and non-string is not allowed",
-                            operator
-                        ),
-                    );
-                    Value::Nil
-                }
+                Operator::Plus => Ok(Value::String(format!("{}{}", l, r).into())),
+                Operator::Star => self.repeat_string(&l, &r, line, column),
+                _ => self.fail(Self::type_mismatch(
+                    operator,
+                    line,
+                    column,
+                    format!(
+                        "Using {} with string and non-string is not allowed",
+                        operator
+                    ),
+                )),
             },
+            _ => self.fail(Self::type_mismatch(
+                operator,
+                line,
+                column,
+                "Cannot do binary operations on Boolean, Nil or Map types".to_string(),
+            )),
+        }
+    }
+    /// Evaluates `string * count` or `count * string`, repeating the string.
+    ///
+    /// The count must be a non-negative integer; a count of zero gives the
+    /// empty string. Results longer than `MAX_STRING_LENGTH` bytes are
+    /// reported as an overflow instead of being allocated.
+    fn repeat_string(
+        &mut self,
+        string: &str,
+        count: &Value,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        match as_integer(count).and_then(|n| usize::try_from(n).ok()) {
+            Some(n)
+                if string
+                    .len()
+                    .checked_mul(n)
+                    .is_none_or(|len| len > MAX_STRING_LENGTH) =>
+            {
+                self.fail(RuntimeError::Overflow {
+                    line,
+                    column,
+                    length: operator_width(&Operator::Star),
+                    message: format!(
+                        "String repetition result is longer than {} bytes",
+                        MAX_STRING_LENGTH
+                    ),
+                })
+            }
+            Some(n) => Ok(Value::String(string.repeat(n).into())),
+            None => self.fail(Self::type_mismatch(
+                &Operator::Star,
+                line,
+                column,
+                format!(
+                    "String repetition count must be a non-negative integer, got {}",
+                    count
+                ),
+            )),
+        }
+    }
+
+    /// Evaluates a bitwise operator on integer-valued numbers.
+    ///
+    /// Both operands are converted to `i64`; numbers with a fractional part,
+    /// shift amounts outside `0..64` and left shifts pushing bits out of the
+    /// `i64` are reported as errors.
+    fn evaluate_bitwise(
+        &mut self,
+        left_val: Value,
+        operator: &Operator,
+        right_val: Value,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        let (l, r) = match (as_integer(&left_val), as_integer(&right_val)) {
+            (Some(l), Some(r)) => (l, r),
             _ => {
-                self.error_reporter.error(
+                return self.fail(Self::type_mismatch(
+                    operator,
                     line,
                     column,
-                    "Cannot do binary operations on Boolean or Nil types",
-                );
-                Value::Nil
+                    format!(
+                        "Operands of {} must be integers, got [{}, {}]",
+                        operator, left_val, right_val
+                    ),
+                ))
             }
+        };
+        let result = match operator {
+            Operator::Amp => Ok(l & r),
+            Operator::Pipe => Ok(l | r),
+            Operator::Caret => Ok(l ^ r),
+            Operator::Shl | Operator::Shr => match u32::try_from(r).ok().filter(|r| *r < i64::BITS)
+            {
+                None => Err(format!("Shift amount {} is out of range", r)),
+                Some(shift) if *operator == Operator::Shr => Ok(l >> shift),
+                // Shifting back has to restore the operand, or bits were lost.
+                Some(shift) if (l << shift) >> shift != l => Err(format!(
+                    "Integer overflow: {} << {} does not fit in 64 bits",
+                    l, r
+                )),
+                Some(shift) => Ok(l << shift),
+            },
+            _ => unreachable!("Operator is not part of bitwise operators"),
+        };
+        match result {
+            Ok(n) => Ok(Value::Number(n as f64)),
+            Err(message) => self.fail(RuntimeError::Overflow {
+                line,
+                column,
+                length: operator_width(operator),
+                message,
+            }),
         }
     }
+
     fn evaluate_comparator(
         &mut self,
         left_val: Value,
@@ -311,59 +991,118 @@ impl Interpreter {
         right_val: Value,
         line: usize,
         column: usize,
-    ) -> Value {
+    ) -> Result<Value, RuntimeError> {
         match (left_val, right_val) {
-            (Value::Number(l), Value::Number(r)) => match operator {
+            (Value::Number(l), Value::Number(r)) => Ok(match operator {
                 Operator::Greater => Value::Boolean(l > r),
                 Operator::GreaterEqual => Value::Boolean(l >= r),
                 Operator::Less => Value::Boolean(l < r),
                 Operator::LessEqual => Value::Boolean(l <= r),
                 _ => unreachable!("Operator is not part of Comparators"),
-            },
-            _ => {
-                self.error_reporter
-                    .error(line, column, "Cannot use comparators on non-numbers");
-                Value::Nil
-            }
+            }),
+            _ => self.fail(Self::type_mismatch(
+                operator,
+                line,
+                column,
+                "Cannot use comparators on non-numbers".to_string(),
+            )),
         }
     }
 
     fn evaluate_equals(&self, left_val: Value, operator: &Operator, right_val: Value) -> Value {
         match operator {
-            Operator::BangEqual => Value::Boolean(left_val != right_val),
-            Operator::EqualEqual => Value::Boolean(left_val == right_val),
+            Operator::BangEqual => Value::Boolean(!values_equal(&left_val, &right_val)),
+            Operator::EqualEqual => Value::Boolean(values_equal(&left_val, &right_val)),
             _ => unreachable!("Operator is not part of Equality"),
         }
     }
 
+    /// Evaluates `and`, `or` or `??`, yielding one of the operands rather than a boolean.
+    ///
+    /// The right operand is only evaluated when it decides the result: `nil and x`
+    /// and `1 or x` return their left operand without touching `x`, while
+    /// `nil or "x"` returns `"x"` and `false or nil` returns `nil`. `??` only
+    /// falls back to its right operand for nil, so `false ?? 1` is `false`.
     fn evaluate_logical(
         &mut self,
         left: &Expression,
         logic_op: &TokenType,
         right: &Expression,
-    ) -> Value {
-        let left_val = self.evaluate_expression(left);
-        match (logic_op, self.is_truthy(&left_val)) {
-            (TokenType::And, true) | (TokenType::Or, false) => self.evaluate_expression(right),
-            _ => left_val,
+    ) -> Result<Value, RuntimeError> {
+        let left_val = self.evaluate_expression(left)?;
+        let needs_right = match logic_op {
+            TokenType::And => self.is_truthy(&left_val),
+            TokenType::Operator(Operator::QuestionQuestion) => left_val == Value::Nil,
+            _ => !self.is_truthy(&left_val),
+        };
+        if needs_right {
+            self.evaluate_expression(right)
+        } else {
+            Ok(left_val)
         }
     }
 
-    fn evaluate_assignment(&mut self, identifier: &str, value: &Expression) -> Value {
-        let evaluated_value = self.evaluate_expression(value);
+    fn evaluate_assignment(
+        &mut self,
+        identifier: Symbol,
+        value: &Expression,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        let evaluated_value = self.evaluate_expression(value)?;
         match self
             .environment_stack
             .assign(identifier, evaluated_value.clone())
         {
-            Ok(()) => evaluated_value,
-            Err(_) => {
-                self.error_reporter.error(
-                    value.line,
-                    value.column,
-                    &format!("Undefined variable '{}' in assignment.", identifier),
-                );
-                Value::Nil
-            }
+            Ok(()) => Ok(evaluated_value),
+            Err(error) => self.fail(Self::assignment_error(error, identifier, line, column)),
+        }
+    }
+
+    /// Turns an environment's refusal to assign `identifier` into the runtime
+    /// error to report at the assignment.
+    fn assignment_error(
+        error: EnvironmentError,
+        identifier: Symbol,
+        line: usize,
+        column: usize,
+    ) -> RuntimeError {
+        match error {
+            EnvironmentError::ConstantVariable => RuntimeError::ConstantAssignment {
+                name: identifier,
+                line,
+                column,
+            },
+            _ => RuntimeError::UndefinedVariable {
+                name: identifier,
+                line,
+                column,
+            },
+        }
+    }
+
+    fn evaluate_global_assignment(
+        &mut self,
+        identifier: Symbol,
+        value: &Expression,
+        line: usize,
+        column: usize,
+    ) -> Result<Value, RuntimeError> {
+        let evaluated_value = self.evaluate_expression(value)?;
+        match self
+            .environment_stack
+            .assign_at_global(identifier, evaluated_value.clone())
+        {
+            Ok(()) => Ok(evaluated_value),
+            Err(error) => self.fail(Self::assignment_error(error, identifier, line, column)),
+        }
+    }
+
+    /// Formats a value the way `print` shows it, applying the float precision.
+    fn display_value(&self, value: &Value) -> String {
+        match (value, self.float_precision) {
+            (Value::Number(n), Some(precision)) => format_with_precision(*n, precision),
+            _ => value.display_for_print(),
         }
     }
 
@@ -376,3 +1115,160 @@ impl Interpreter {
         }
     }
 }
+
+/// Names an expression in the evaluation trace, with its operator or
+/// variable if it has one, e.g. `Binary +` or `Variable x`.
+fn trace_label(kind: &ExprKind) -> String {
+    match kind {
+        ExprKind::Lit { .. } => "Literal".to_string(),
+        ExprKind::Var { identifier } => format!("Variable {}", identifier),
+        ExprKind::Grouping { .. } => "Grouping".to_string(),
+        ExprKind::BlockExpr { .. } => "Block".to_string(),
+        ExprKind::MapLit { .. } => "Map".to_string(),
+        ExprKind::Index { .. } => "Index".to_string(),
+        ExprKind::Unary { operator, .. } => format!("Unary {}", operator),
+        ExprKind::TypeOf { .. } => "TypeOf".to_string(),
+        ExprKind::IncDec {
+            identifier,
+            operator,
+            ..
+        } => format!("IncDec {} {}", operator, identifier),
+        ExprKind::Binary { operator, .. } => format!("Binary {}", operator),
+        ExprKind::Logical { logic_op, .. } => format!("Logical {}", logic_op),
+        ExprKind::Assignment { identifier, .. } => format!("Assignment {}", identifier),
+        ExprKind::IndexAssignment { .. } => "IndexAssignment".to_string(),
+        ExprKind::GlobalAssignment { identifier, .. } => {
+            format!("GlobalAssignment {}", identifier)
+        }
+        ExprKind::Sequence { .. } => "Sequence".to_string(),
+    }
+}
+
+/// Names a statement in the evaluation trace.
+fn statement_label(kind: &StmtKind) -> &'static str {
+    match kind {
+        StmtKind::ExprStmt { .. } => "Expression",
+        StmtKind::IfStmt { .. } => "If",
+        StmtKind::WhileStmt { .. } => "While",
+        StmtKind::ForStmt { .. } => "For",
+        StmtKind::PrintStmt { .. } => "Print",
+        StmtKind::Break { .. } => "Break",
+        StmtKind::Continue { .. } => "Continue",
+        StmtKind::Switch { .. } => "Switch",
+        StmtKind::Block { .. } => "Block",
+    }
+}
+
+/// Lists the bodies a switch runs when the case at `matched` matches, or the
+/// default case when none does.
+///
+/// Bodies follow each other while a case ends with `fallthrough;`; the last
+/// case falls through into the default case.
+fn switch_arms<'a>(
+    cases: &'a [SwitchCase],
+    default: &'a Option<Vec<Declaration>>,
+    matched: Option<usize>,
+) -> Vec<&'a Vec<Declaration>> {
+    let Some(start) = matched else {
+        return default.iter().collect();
+    };
+    let mut arms = vec![];
+    for case in &cases[start..] {
+        arms.push(&case.body);
+        if !case.fallthrough {
+            return arms;
+        }
+    }
+    arms.extend(default.iter());
+    arms
+}
+
+/// Returns how many characters an operator takes up in the source.
+fn operator_width(operator: &Operator) -> usize {
+    operator.to_string().chars().count()
+}
+
+/// Converts a value to an integer if it is a number without a fractional part.
+fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= i64::MAX as f64 => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// Rounds `n` to `precision` decimal places and trims trailing zeros, along
+/// with the decimal point if nothing is left after it.
+fn format_with_precision(n: f64, precision: usize) -> String {
+    if !n.is_finite() {
+        return format_number(n);
+    }
+    let rounded = format!("{:.*}", precision, n);
+    if !rounded.contains('.') {
+        return rounded;
+    }
+    match rounded.trim_end_matches('0').trim_end_matches('.') {
+        // Small negative numbers round to zero and should not keep their sign.
+        "-0" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// Decides whether two values are equal under Lox's `==`.
+///
+/// - Values of different types are never equal, so `1 == "1"` is false rather
+///   than an error, and no coercion takes place.
+/// - `nil` only equals `nil`.
+/// - Numbers follow IEEE 754, so `NaN` is not equal to anything, itself included.
+/// - Strings and booleans are equal when their contents are.
+/// - Maps are equal when they have the same keys with equal values, compared
+///   recursively. A pair of maps met again while comparing them, as happens
+///   with maps that contain themselves, is taken to be equal, so cycles end
+///   the comparison instead of hanging it. Beyond `MAX_EQUALITY_DEPTH` nested
+///   maps, maps are only equal to themselves.
+pub fn values_equal(left: &Value, right: &Value) -> bool {
+    values_equal_within(left, right, &mut Vec::new())
+}
+
+/// How many maps deep `values_equal` compares contents before falling back
+/// to comparing identity, to keep the recursion off the native stack limit.
+pub const MAX_EQUALITY_DEPTH: usize = 100;
+
+/// Identifies a map by its address, which is shared by all its copies.
+type MapAddress = *const RefCell<HashMap<MapKey, Value>>;
+
+/// Compares two values like `values_equal`, where `comparing` holds the
+/// pairs of maps whose comparison is under way further up.
+fn values_equal_within(
+    left: &Value,
+    right: &Value,
+    comparing: &mut Vec<(MapAddress, MapAddress)>,
+) -> bool {
+    match (left, right) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Number(l), Value::Number(r)) => l == r,
+        // Copies of one string, such as repeated literals, share their text.
+        (Value::String(l), Value::String(r)) => Rc::ptr_eq(l, r) || l == r,
+        (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::Map(l), Value::Map(r)) => {
+            let pair = (Rc::as_ptr(l), Rc::as_ptr(r));
+            if Rc::ptr_eq(l, r) || comparing.contains(&pair) {
+                return true;
+            }
+            if comparing.len() >= MAX_EQUALITY_DEPTH {
+                return false;
+            }
+            let (l, r) = (l.borrow(), r.borrow());
+            if l.len() != r.len() {
+                return false;
+            }
+            comparing.push(pair);
+            let equal = l.iter().all(|(key, value)| {
+                r.get(key)
+                    .is_some_and(|other| values_equal_within(value, other, comparing))
+            });
+            comparing.pop();
+            equal
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,18 @@
+//! A tree-walk interpreter for the Lox language.
+//!
+//! The crate exposes every stage of the pipeline (scanning, parsing, printing
+//! and interpretation) so that the `lox` binary and other tools can share them.
+
+pub mod ast;
+pub mod constant_folder;
+pub mod environment;
+pub mod error_reporter;
+pub mod formatter;
+pub mod interner;
+pub mod interpreter;
+pub mod lints;
+pub mod parser;
+pub mod pretty_printer;
+pub mod rpn_printer;
+pub mod scanner;
+pub mod token;
@@ -0,0 +1,183 @@
+//! Implements optional static checks for Lox programs.
+//!
+//! This module contains the `Linter` struct, which walks a parsed program
+//! without executing it and reports suspicious code as warnings.
+//! Warnings never cause the program to be rejected.
+use crate::ast::{
+    walk_expr, walk_program, walk_stmt, walk_var_decl, DeclKind, Declaration, ExprKind, Expression,
+    Program, Statement, StmtKind, VarDecl, Visitor,
+};
+use crate::{error_reporter::ErrorReporter, interner::Symbol};
+
+/// A variable declared in one of the scopes tracked by the linter.
+struct Binding {
+    identifier: Symbol,
+    line: usize,
+    column: usize,
+    used: bool,
+}
+
+/// Static analysis pass reporting unused variables and unreachable code.
+///
+/// A variable counts as used when its value is read somewhere in its scope.
+/// Assigning to a variable does not count as a use, and names starting with
+/// an underscore are never reported.
+pub struct Linter {
+    /// Stack of scopes, innermost last, each holding its bindings in declaration order.
+    scopes: Vec<Vec<Binding>>,
+    /// Handles reporting of lint warnings
+    pub error_reporter: ErrorReporter,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linter {
+    /// Creates a new Linter instance
+    pub fn new() -> Self {
+        Linter {
+            scopes: vec![],
+            error_reporter: ErrorReporter::new(),
+        }
+    }
+
+    /// Checks a whole program, treating its top level as the global scope.
+    pub fn check_program(&mut self, program: &Program) {
+        self.check_reachability(program);
+        self.with_scope(|linter| walk_program(linter, program));
+    }
+
+    /// Warns about the first declaration after a `break` or `continue` in the
+    /// same list of declarations, as it can never run.
+    ///
+    /// Only jumps directly in the list count, so the code after an `if`
+    /// containing a jump, which may not be taken, is never reported.
+    fn check_reachability(&mut self, declarations: &[Declaration]) {
+        let jump = declarations.iter().position(|declaration| {
+            matches!(
+                &declaration.kind,
+                DeclKind::Statement(Statement {
+                    kind: StmtKind::Break { .. } | StmtKind::Continue { .. },
+                    ..
+                })
+            )
+        });
+        if let Some(unreachable) = jump.and_then(|index| declarations.get(index + 1)) {
+            self.error_reporter
+                .warning(unreachable.line, unreachable.column, "Unreachable code.");
+        }
+    }
+
+    /// Runs `check` inside a fresh scope and reports the scope's unused bindings afterwards.
+    fn with_scope<F>(&mut self, check: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.scopes.push(vec![]);
+        check(self);
+        if let Some(scope) = self.scopes.pop() {
+            for binding in scope {
+                self.report_if_unused(&binding);
+            }
+        }
+    }
+
+    /// Adds a binding to the innermost scope.
+    ///
+    /// Redeclaring a name in the same scope replaces the earlier binding,
+    /// which is reported first if it was never used.
+    fn declare(&mut self, var_decl: &VarDecl) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let binding = Binding {
+            identifier: var_decl.identifier,
+            line: var_decl.line,
+            column: var_decl.column,
+            used: false,
+        };
+        let previous = scope
+            .iter()
+            .position(|b| b.identifier == var_decl.identifier)
+            .map(|index| scope.remove(index));
+        scope.push(binding);
+        if let Some(previous) = previous {
+            self.report_if_unused(&previous);
+        }
+    }
+
+    /// Marks the innermost binding with the given name as used.
+    fn mark_used(&mut self, identifier: Symbol) {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().find(|b| b.identifier == identifier));
+        if let Some(binding) = binding {
+            binding.used = true;
+        }
+    }
+
+    fn report_if_unused(&mut self, binding: &Binding) {
+        if !binding.used && !binding.identifier.with_str(|name| name.starts_with('_')) {
+            self.error_reporter.warning(
+                binding.line,
+                binding.column,
+                &format!("Unused variable: {}", binding.identifier),
+            );
+        }
+    }
+}
+
+impl Visitor for Linter {
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) {
+        // The initializer is resolved before the name is declared, so `var x = x;`
+        // reads the `x` of an enclosing scope.
+        walk_var_decl(self, var_decl);
+        self.declare(var_decl);
+    }
+
+    fn visit_stmt(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StmtKind::ForStmt { .. } => self.with_scope(|linter| walk_stmt(linter, statement)),
+            StmtKind::Block { declarations } => {
+                self.check_reachability(declarations);
+                self.with_scope(|linter| walk_stmt(linter, statement))
+            }
+            // Each case body is a scope of its own.
+            StmtKind::Switch {
+                discriminant,
+                cases,
+                default,
+            } => {
+                self.visit_expr(discriminant);
+                for case in cases {
+                    self.visit_expr(&case.value);
+                    self.check_reachability(&case.body);
+                    self.with_scope(|linter| walk_program(linter, &case.body));
+                }
+                if let Some(body) = default {
+                    self.check_reachability(body);
+                    self.with_scope(|linter| walk_program(linter, body));
+                }
+            }
+            _ => walk_stmt(self, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExprKind::Var { identifier } | ExprKind::IncDec { identifier, .. } => {
+                self.mark_used(*identifier)
+            }
+            ExprKind::BlockExpr { declarations, .. } => {
+                self.check_reachability(declarations);
+                self.with_scope(|linter| walk_expr(linter, expression))
+            }
+            _ => walk_expr(self, expression),
+        }
+    }
+}
@@ -3,50 +3,250 @@
 //! This module ties together all components of the Lox interpreter and provides
 //! the command-line interface for running Lox programs or starting an interactive REPL.
 
-mod ast;
-mod environment;
-mod error_reporter;
-mod interpreter;
-mod parser;
-mod pretty_printer;
-mod scanner;
-mod token;
-
 use std::{
     env, fs,
     io::{self, Write},
+    mem,
+    panic::{self, PanicHookInfo},
     process,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use lox_tree_walk_interpreter::{
+    ast::Program,
+    constant_folder::ConstantFolder,
+    error_reporter::ErrorReporter,
+    formatter::Formatter,
+    interpreter::{last_position, Interpreter},
+    lints::Linter,
+    parser::{needs_more_input, Parser},
+    pretty_printer::PrettyPrinter,
+    scanner::{scan, Scanner},
 };
 
-use ast::Program;
-use error_reporter::ErrorReporter;
-use interpreter::Interpreter;
-use parser::Parser;
-use pretty_printer::PrettyPrinter;
-use scanner::Scanner;
+/// Stack size of the thread running Lox code.
+///
+/// Scanning, parsing and interpretation are all recursive, so deeply nested
+/// programs need more room than the main thread's default stack to reach the
+/// interpreter's depth limit instead of overflowing the native stack.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The REPL prompt shown when `--prompt` is not given.
+const DEFAULT_PROMPT: &str = "> ";
+
+/// The REPL prompt shown for the further lines of unfinished input when
+/// `--continuation-prompt` is not given.
+const DEFAULT_CONTINUATION_PROMPT: &str = ". ";
+
+/// Exit code for a script with syntax errors, following `EX_DATAERR`.
+const EXIT_SYNTAX_ERROR: i32 = 65;
+
+/// Exit code for a script that hit a runtime error, following `EX_SOFTWARE`
+/// as the reference Lox implementation does.
+const EXIT_RUNTIME_ERROR: i32 = 70;
+
+/// Exit code for a bug in the interpreter itself, the code Rust exits with
+/// on a panic, so it cannot be mistaken for an error in the script.
+const EXIT_INTERNAL_ERROR: i32 = 101;
+
+/// Environment variable that makes a script run panic once the program has
+/// run, so tests can check how internal errors are reported.
+const PANIC_AFTER_RUN_VARIABLE: &str = "LOX_PANIC_AFTER_RUN";
+
+/// How diagnostics are written to stderr.
+#[derive(Default, PartialEq)]
+enum ErrorFormat {
+    /// One readable message per diagnostic, followed by the offending source line.
+    #[default]
+    Human,
+    /// One JSON object per line, for tools.
+    Json,
+}
+
+/// Command-line options accepted by the interpreter.
+#[derive(Default)]
+struct Options {
+    /// Abort on reads of uninitialized variables instead of yielding `nil`.
+    strict: bool,
+    /// Run the static checks and report their warnings before executing.
+    lint: bool,
+    /// Decimal places `print` rounds numbers to, or `None` for full precision.
+    precision: Option<usize>,
+    /// Iterations a single loop may run before the program is aborted, or `None` for no limit.
+    max_loop_iterations: Option<usize>,
+    /// Fold constant expressions before running the program.
+    fold: bool,
+    /// Print the program as formatted source instead of running it.
+    format: bool,
+    /// Only scan and parse the program, reporting its syntax errors.
+    check: bool,
+    /// Check that formatting the program and parsing it again gives back the
+    /// same program, instead of running it.
+    verify: bool,
+    /// Print the scanned tokens as JSON lines instead of running the program.
+    tokens_json: bool,
+    /// How diagnostics are reported.
+    error_format: ErrorFormat,
+    /// Report how long scanning, parsing and interpretation took on stderr.
+    time: bool,
+    /// Print a trace of the evaluation on stderr.
+    trace: bool,
+    /// The REPL prompt, or `None` for `DEFAULT_PROMPT`.
+    prompt: Option<String>,
+    /// The REPL prompt for unfinished input, or `None` for `DEFAULT_CONTINUATION_PROMPT`.
+    continuation_prompt: Option<String>,
+    /// The script to run, or `None` to start the REPL.
+    script: Option<String>,
+}
+
+impl Options {
+    /// Parses the command-line arguments, excluding the program name.
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        for arg in args {
+            match arg.as_str() {
+                "--strict" => options.strict = true,
+                "--lint" => options.lint = true,
+                "--time" => options.time = true,
+                "--trace" => options.trace = true,
+                "--fold" => options.fold = true,
+                "--format" => options.format = true,
+                "--check" => options.check = true,
+                "--verify" => options.verify = true,
+                "--tokens-json" => options.tokens_json = true,
+                "--error-format=human" => options.error_format = ErrorFormat::Human,
+                "--error-format=json" => options.error_format = ErrorFormat::Json,
+                flag if flag.starts_with("--precision=") => {
+                    let digits = &flag["--precision=".len()..];
+                    options.precision = Some(digits.parse().map_err(|_| {
+                        format!("Invalid precision '{}', expected a number", digits)
+                    })?);
+                }
+                flag if flag.starts_with("--prompt=") => {
+                    options.prompt = Some(flag["--prompt=".len()..].to_string());
+                }
+                flag if flag.starts_with("--continuation-prompt=") => {
+                    options.continuation_prompt =
+                        Some(flag["--continuation-prompt=".len()..].to_string());
+                }
+                flag if flag.starts_with("--max-loop-iterations=") => {
+                    let count = &flag["--max-loop-iterations=".len()..];
+                    options.max_loop_iterations = Some(count.parse().map_err(|_| {
+                        format!("Invalid loop limit '{}', expected a number", count)
+                    })?);
+                }
+                flag if flag.starts_with("--") => {
+                    return Err(format!("Unknown option '{}'", flag));
+                }
+                _ if options.script.is_none() => options.script = Some(arg.clone()),
+                _ => return Err("Too many arguments".to_string()),
+            }
+        }
+        Ok(options)
+    }
+}
 
 /// The main entry point for the Lox interpreter.
 ///
 /// Handles command-line arguments to either run a Lox file or start an interactive REPL.
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        1 => run_prompt(),
-        2 => run_file(&args[1]),
-        _ => {
-            eprintln!("Usage: lox [script]");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!(
+                "Usage: lox [--strict] [--lint] [--time] [--trace] [--fold] [--format] \
+                 [--check] [--verify] [--tokens-json] [--error-format=human|json] [--precision=<digits>] \
+                 [--max-loop-iterations=<count>] [--prompt=<text>] \
+                 [--continuation-prompt=<text>] [script]"
+            );
             process::exit(64);
         }
+    };
+    let json = options.error_format == ErrorFormat::Json;
+    panic::set_hook(Box::new(move |info| report_internal_error(info, json)));
+    let interpreter_thread = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || match &options.script {
+            Some(filename) => run_file(filename, &options),
+            None => run_prompt(&options),
+        })
+        .expect("Failed to spawn interpreter thread");
+    // Joining catches a panic unwinding out of the thread, so a bug in the
+    // interpreter ends the process cleanly after the hook has reported it.
+    if interpreter_thread.join().is_err() {
+        process::exit(EXIT_INTERNAL_ERROR);
     }
 }
 
+/// Reports a panic as a single internal error diagnostic instead of Rust's
+/// default panic message, in the format chosen with `--error-format`.
+///
+/// The diagnostic points at the statement the interpreter executed last, or
+/// at line 0 if the panic happened before any code ran.
+fn report_internal_error(info: &PanicHookInfo, json: bool) {
+    let payload = info.payload();
+    let cause = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    let (line, column) = last_position().unwrap_or((0, 0));
+    let mut error_reporter = if json {
+        ErrorReporter::collecting()
+    } else {
+        ErrorReporter::new()
+    };
+    error_reporter.error(line, column, &format!("Internal error: {}", cause));
+    emit_diagnostics(&mut error_reporter, "internal");
+}
+
+/// The REPL meta-commands and their descriptions, as listed by `.help`.
+const META_COMMANDS: [(&str, &str); 5] = [
+    (".help", "List the available commands"),
+    (
+        ".tokens <source>",
+        "Show the tokens scanned from the rest of the line",
+    ),
+    (
+        ".ast <source>",
+        "Show the parsed program for the rest of the line",
+    ),
+    (".vars", "Show the variables defined so far"),
+    (".clear", "Forget all variables defined so far"),
+];
+
 /// Starts an interactive REPL (Read-Eval-Print Loop) for Lox.
 ///
 /// This function repeatedly prompts the user for input, executes the input,
-/// and displays the result until an empty line is entered.
-fn run_prompt() {
+/// and displays the result until an empty line is entered. Variables persist
+/// from one line to the next, and errors are reported without ending the
+/// session. Lines starting with `.` are meta-commands rather than Lox code.
+///
+/// Input that stops partway through, such as an open brace or a statement
+/// without its `;`, is continued on the next lines under the continuation
+/// prompt and run as one unit once it is complete. An empty line runs it
+/// as it is.
+fn run_prompt(options: &Options) {
+    let prompt = options.prompt.as_deref().unwrap_or(DEFAULT_PROMPT);
+    let continuation_prompt = options
+        .continuation_prompt
+        .as_deref()
+        .unwrap_or(DEFAULT_CONTINUATION_PROMPT);
+    let mut interpreter = create_interpreter(options);
+    let mut source = String::new();
     loop {
-        print!("> ");
+        print!(
+            "{}",
+            if source.is_empty() {
+                prompt
+            } else {
+                continuation_prompt
+            }
+        );
         io::stdout()
             .flush()
             .expect("Failed to flush stdout, Critical I/O error");
@@ -56,27 +256,97 @@ fn run_prompt() {
             .read_line(&mut input)
             .expect("Failed to read line: Critical I/O error");
 
-        if input.trim().is_empty() {
-            break;
-        } else {
-            run(input);
+        let line = input.trim();
+        if source.is_empty() {
+            if line.is_empty() {
+                break;
+            } else if line.starts_with('.') {
+                run_meta_command(line, options, &mut interpreter);
+                continue;
+            }
+        }
+        source.push_str(&input);
+        if !line.is_empty() && needs_more_input(&source) {
+            continue;
+        }
+        run(&source, options, &mut interpreter, true);
+        source.clear();
+    }
+}
+
+/// Executes a REPL meta-command such as `.help` or `.tokens print 1;`.
+///
+/// Unknown commands are reported without ending the session.
+fn run_meta_command(line: &str, options: &Options, interpreter: &mut Interpreter) {
+    let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+        ".help" => {
+            for (usage, description) in META_COMMANDS {
+                println!("{:<18} {}", usage, description);
+            }
+        }
+        ".tokens" => {
+            let mut scanner = Scanner::new(argument);
+            for token in scanner.scan_tokens() {
+                println!("{}", token);
+            }
+        }
+        ".ast" => {
+            let mut scanner = Scanner::new(argument);
+            let tokens = scanner.scan_tokens();
+            if scanner.error_reporter.had_error() {
+                return;
+            }
+            let program = Parser::new(&tokens).parse_program();
+            println!("{}", PrettyPrinter::new().print_program(&program));
+        }
+        ".vars" => {
+            let bindings = interpreter.environment_stack.dump();
+            if !bindings.is_empty() {
+                println!("{}", bindings);
+            }
         }
+        ".clear" => *interpreter = create_interpreter(options),
+        _ => eprintln!(
+            "Unknown command '{}'. Type .help for a list of commands.",
+            command
+        ),
     }
 }
 
+/// Creates an interpreter configured by the command-line options.
+fn create_interpreter(options: &Options) -> Interpreter {
+    Interpreter::new()
+        .strict(options.strict)
+        .with_float_precision(options.precision)
+        .with_loop_limit(options.max_loop_iterations)
+        .with_trace(options.trace)
+}
+
 /// Runs a Lox program from a file.
 ///
 /// # Arguments
 ///
 /// * `filename` - The path to the Lox source file to execute.
+/// * `options` - The command-line options to run with.
 ///
 /// # Exits
 ///
+/// * Exit code 65: If the program had a syntax error.
+/// * Exit code 70: If the program had a runtime error.
 /// * Exit code 66: If the file is not found.
 /// * Exit code 74: For any other file reading errors.
-fn run_file(filename: &str) {
+fn run_file(filename: &str, options: &Options) {
     match fs::read_to_string(filename) {
-        Ok(contents) => run(contents),
+        Ok(contents) => {
+            let mut interpreter = create_interpreter(options);
+            if !run(&contents, options, &mut interpreter, false) {
+                if interpreter.error_reporter.had_runtime_error() {
+                    process::exit(EXIT_RUNTIME_ERROR);
+                }
+                process::exit(EXIT_SYNTAX_ERROR);
+            }
+        }
         Err(e) => {
             if e.kind() == io::ErrorKind::NotFound {
                 eprintln!("Error: File '{}' not found", filename);
@@ -94,46 +364,187 @@ fn run_file(filename: &str) {
 /// This function orchestrates the entire interpretation process:
 /// 1. Scanning (lexical analysis)
 /// 2. Parsing (syntax analysis)
-/// 3. Pretty printing (for debugging)
-/// 4. Interpretation (execution)
+/// 3. Linting (optional static checks), unless only checking the syntax
+/// 4. Constant folding (optional)
+/// 5. Formatting (optional, replacing the remaining phases)
+/// 6. Pretty printing (for debugging)
+/// 7. Interpretation (execution)
 ///
 /// # Arguments
 ///
 /// * `contents` - A string slice containing Lox source code to execute.
-fn run(contents: String) {
+/// * `options` - The command-line options to run with.
+/// * `interpreter` - The interpreter to run the code in, keeping any
+///   variables it defined for later calls.
+/// * `echo` - Whether to print the value of a trailing expression statement,
+///   as the REPL does.
+///
+/// # Returns
+///
+/// `false` if any phase reported an error, in which case later phases are skipped.
+fn run(contents: &str, options: &Options, interpreter: &mut Interpreter, echo: bool) -> bool {
+    // Every phase shows the offending source line under its diagnostics.
+    let source: Rc<str> = Rc::from(contents);
+
     // Scanning
-    let mut scanner = Scanner::new(&contents);
+    let start = Instant::now();
+    let mut scanner = Scanner::new(contents);
+    scanner.error_reporter = create_error_reporter(options, &source);
     let tokens = scanner.scan_tokens();
-    check(scanner.error_reporter);
+    emit_diagnostics(&mut scanner.error_reporter, "scan");
+    report_time(options, "scanning", start.elapsed());
+    if scanner.error_reporter.had_error() {
+        return false;
+    }
+    if options.tokens_json {
+        for token in &tokens {
+            println!("{}", token.to_json());
+        }
+        return true;
+    }
 
     // Parsing
+    let start = Instant::now();
     let mut parser = Parser::new(&tokens);
-    let program: Program = parser.parse_program();
-    check(parser.error_reporter);
+    parser.error_reporter = create_error_reporter(options, &source);
+    let mut program: Program = parser.parse_program();
+    emit_diagnostics(&mut parser.error_reporter, "parse");
+    report_time(options, "parsing", start.elapsed());
+    if parser.error_reporter.had_error() {
+        return false;
+    }
+    if options.check {
+        return true;
+    }
+    if options.verify {
+        if let Err(message) = verify_round_trip(&program) {
+            eprintln!("Error: {}", message);
+            return false;
+        }
+        return true;
+    }
+
+    // Static checks (warnings only)
+    if options.lint {
+        let mut linter = Linter::new();
+        linter.error_reporter = create_error_reporter(options, &source);
+        linter.check_program(&program);
+        emit_diagnostics(&mut linter.error_reporter, "lint");
+    }
+
+    // Constant folding (optional)
+    if options.fold {
+        ConstantFolder::new().fold_program(&mut program);
+    }
+
+    // Formatting (optional, the program is not run)
+    if options.format {
+        let formatter = Formatter::new().with_comments(mem::take(&mut scanner.comments));
+        println!("{}", formatter.format_program(&program));
+        return true;
+    }
 
     // Pretty printing (for debugging)
-    let pretty_printer = PrettyPrinter::new();
+    let mut pretty_printer = PrettyPrinter::new();
     println!("{}", pretty_printer.print_program(&program));
 
     // Interpretation
-    let mut interpreter = Interpreter::new();
-    interpreter.evaluate_program(&program);
-    check(interpreter.error_reporter);
+    let start = Instant::now();
+    // Errors of earlier runs in the same interpreter must not fail this one.
+    interpreter.error_reporter = create_error_reporter(options, &source);
+    if echo {
+        let value = interpreter.evaluate_repl_program(&program);
+        emit_diagnostics(&mut interpreter.error_reporter, "runtime");
+        // A value produced after a reported error is only a stand-in.
+        if let Some(value) = value.filter(|_| !interpreter.error_reporter.had_error()) {
+            println!("{}", value.repl_display());
+        }
+    } else {
+        // The first fatal error has been reported and ended the program.
+        let _ = interpreter.evaluate_program(&program);
+        emit_diagnostics(&mut interpreter.error_reporter, "runtime");
+    }
+    report_time(options, "interpretation", start.elapsed());
+    if env::var_os(PANIC_AFTER_RUN_VARIABLE).is_some() {
+        panic!("{} is set", PANIC_AFTER_RUN_VARIABLE);
+    }
+    !interpreter.error_reporter.had_error()
 }
 
-/// Checks if any errors were reported during execution.
+/// Formats `program`, parses the formatted source again and checks that the
+/// result is the same program, as a self-check of the parser and formatter.
 ///
-/// If errors were found, exits the program with code 65.
+/// Declarations are compared with `struct_eq`, which ignores positions and
+/// groupings, as the formatter moves code around and only keeps the
+/// parentheses precedence requires. A differing declaration is shown in its
+/// pretty-printed form.
 ///
-/// # Arguments
+/// # Returns
 ///
-/// * `error_reporter` - The ErrorReporter instance to check for errors.
+/// A message describing the first difference, if there is one.
+fn verify_round_trip(program: &Program) -> Result<(), String> {
+    let formatted = Formatter::new().format_program(program);
+    let (tokens, diagnostics) = scan(&formatted);
+    if let Some(diagnostic) = diagnostics.first() {
+        return Err(format!(
+            "The formatted program does not scan: {}",
+            diagnostic
+        ));
+    }
+    let mut parser = Parser::new(&tokens);
+    parser.error_reporter = ErrorReporter::collecting();
+    let reparsed = parser.parse_program();
+    if let Some(diagnostic) = parser.error_reporter.take_diagnostics().first() {
+        return Err(format!(
+            "The formatted program does not parse: {}",
+            diagnostic
+        ));
+    }
+    let mut printer = PrettyPrinter::new().omit_groupings(true);
+    for (index, (before, after)) in program.iter().zip(&reparsed).enumerate() {
+        if !before.struct_eq(after) {
+            return Err(format!(
+                "Declaration {} changes when formatted and parsed again:\n{}\nbecomes\n{}",
+                index + 1,
+                printer.print_declaration(before),
+                printer.print_declaration(after)
+            ));
+        }
+    }
+    if program.len() != reparsed.len() {
+        return Err(format!(
+            "The program has {} declarations, but {} after formatting and parsing again",
+            program.len(),
+            reparsed.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the error reporter for one phase of `run`.
 ///
-/// # Exits
+/// JSON diagnostics are collected, to be written by `emit_diagnostics` once
+/// the phase is done; readable ones are printed as they are reported.
+fn create_error_reporter(options: &Options, source: &Rc<str>) -> ErrorReporter {
+    match options.error_format {
+        ErrorFormat::Human => ErrorReporter::new().with_source(Rc::clone(source)),
+        ErrorFormat::Json => ErrorReporter::collecting(),
+    }
+}
+
+/// Writes the diagnostics a phase collected to stderr as JSON lines.
 ///
-/// * Exit code 65: If any errors were reported.
-fn check(error_reporter: ErrorReporter) {
-    if error_reporter.had_error() {
-        process::exit(65);
+/// Does nothing for reporters that printed their diagnostics already.
+fn emit_diagnostics(error_reporter: &mut ErrorReporter, phase: &str) {
+    for diagnostic in error_reporter.take_diagnostics() {
+        eprintln!("{}", diagnostic.to_json(phase));
+    }
+}
+
+/// Prints how long a phase took to stderr when `--time` was given,
+/// keeping the report apart from the program's own output.
+fn report_time(options: &Options, phase: &str, elapsed: Duration) {
+    if options.time {
+        eprintln!("[time] {}: {:?}", phase, elapsed);
     }
 }
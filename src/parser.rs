@@ -2,17 +2,122 @@
 //!
 //! This module is responsible for converting the tokens to a single big expression.
 use crate::{
-    ast::{DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind, VarDecl},
-    error_reporter::{ErrorReporter, ParseError},
+    ast::{
+        DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind, SwitchCase,
+        VarDecl,
+    },
+    error_reporter::{tokens_to_source, Diagnostic, ErrorReporter, ParseError},
+    interner::Symbol,
+    scanner::scan,
     token::{Operator, Token, TokenType},
 };
-use std::{iter::Peekable, slice::Iter};
+use std::{collections::HashSet, iter::Peekable, slice::Iter};
+
+/// The declarations of a switch case, with the position of the
+/// `fallthrough` ending it, if any.
+type CaseBody = (Vec<Declaration>, Option<(usize, usize)>);
+
+/// Tokens that are a literal value on their own.
+const LITERAL_TOKENS: [TokenType; 7] = [
+    TokenType::String,
+    TokenType::Number,
+    TokenType::True,
+    TokenType::False,
+    TokenType::Nil,
+    TokenType::Infinity,
+    TokenType::NaN,
+];
+
+/// Tokens that start a statement other than an expression statement.
+const STATEMENT_STARTS: [TokenType; 9] = [
+    TokenType::Print,
+    TokenType::LeftBrace,
+    TokenType::While,
+    TokenType::For,
+    TokenType::If,
+    TokenType::Switch,
+    TokenType::Break,
+    TokenType::Continue,
+    TokenType::Fallthrough,
+];
+
+/// Scans and parses `source` as a single expression without printing anything,
+/// returning the expression or the diagnostics explaining why it isn't one.
+///
+/// The whole input has to be that expression: empty input and tokens left
+/// over after the expression are errors.
+pub fn parse_expr(source: &str) -> Result<Expression, Vec<Diagnostic>> {
+    let (tokens, diagnostics) = scan(source);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    let mut parser = Parser::new(&tokens);
+    parser.error_reporter = ErrorReporter::collecting();
+    let expression = parser.parse_expression();
+    if expression.is_ok() {
+        if let Some(token) = parser.token_iterator.peek() {
+            parser.error_reporter.error_spanning(
+                token.line,
+                token.column,
+                token.length,
+                "Expected end of input after expression.",
+            );
+        }
+    }
+    let diagnostics = parser.error_reporter.take_diagnostics();
+    match expression {
+        Ok(expression) if diagnostics.is_empty() => Ok(expression),
+        _ => Err(diagnostics),
+    }
+}
+
+/// Checks whether `source` stops partway through a declaration, such as a
+/// block whose `}` or a statement whose `;` has not been typed yet, so that
+/// the REPL can wait for more lines instead of reporting an error.
+///
+/// Complete programs, and programs with a syntax error before their end,
+/// need no more input. Nothing is reported either way.
+pub fn needs_more_input(source: &str) -> bool {
+    let (tokens, diagnostics) = scan(source);
+    if !diagnostics.is_empty() {
+        return false;
+    }
+    let mut parser = Parser::new(&tokens);
+    parser.error_reporter = ErrorReporter::collecting();
+    while parser.token_iterator.peek().is_some() {
+        if let Err(error) = parser.parse_declaration() {
+            return error.is_end_of_input();
+        }
+    }
+    false
+}
+
+/// A single REPL line, as classified by `Parser::parse_repl_line`.
+#[derive(Debug)]
+pub enum ReplItem {
+    /// A bare expression without a trailing `;`, whose value can be echoed.
+    Expr(Expression),
+    Stmt(Statement),
+    Decl(Declaration),
+}
 
 /// The parser for Lox expressions.
 ///
 /// Uses a peekable iterator.
 pub struct Parser<'a> {
+    /// All tokens being parsed, for showing the context of errors.
+    tokens: &'a [Token],
     token_iterator: Peekable<Iter<'a, Token>>,
+    /// Labels of the loops enclosing the statement being parsed, innermost last.
+    labels: Vec<Symbol>,
+    /// Number of loops enclosing the statement being parsed, labeled or not.
+    loop_depth: usize,
+    /// Number of switch statements enclosing the statement being parsed.
+    switch_depth: usize,
+    /// Variables declared in each local scope enclosing the statement being
+    /// parsed, innermost last. Empty at the top level, where variables may be
+    /// redeclared.
+    scopes: Vec<HashSet<Symbol>>,
     pub error_reporter: ErrorReporter,
 }
 
@@ -20,7 +125,12 @@ impl<'a> Parser<'a> {
     /// Creates a new Parser instance.    
     pub fn new(token_list: &'a [Token]) -> Self {
         Parser {
+            tokens: token_list,
             token_iterator: token_list.iter().peekable(),
+            labels: vec![],
+            loop_depth: 0,
+            switch_depth: 0,
+            scopes: vec![],
             error_reporter: ErrorReporter::new(),
         }
     }
@@ -30,91 +140,159 @@ impl<'a> Parser<'a> {
         while self.token_iterator.peek().is_some() {
             match self.parse_declaration() {
                 Ok(declaration) => program.push(declaration),
-                Err(_) => match self.synchronize() {
-                    Err(ParseError::UnexpectedEOF) => break,
-                    _ => {}
-                },
+                Err(_) => {
+                    if self.error_reporter.limit_reached() {
+                        break;
+                    }
+                    if let Err(ParseError::UnexpectedEOF) = self.synchronize() {
+                        break;
+                    }
+                }
             }
         }
         program
     }
 
-    pub fn parse_declaration(&mut self) -> Result<Declaration, ParseError> {
-        match self.search(&[TokenType::Var]) {
-            Some(_) => self.parse_var_decl().map(|var_decl| {
-                let line = var_decl.line;
-                let column = var_decl.column;
-                Declaration {
-                    kind: DeclKind::VarDecl(var_decl),
-                    line,
-                    column,
-                }
-            }),
-            None => self.parse_statement().map(|statement| {
-                let line = statement.line;
-                let column = statement.column;
-                Declaration {
-                    kind: DeclKind::Statement(statement),
-                    line,
-                    column,
-                }
-            }),
+    /// Parses a single REPL line, which may be a bare expression such as `1 + 1`
+    /// besides a statement or a declaration.
+    ///
+    /// The line is first tried as an expression that uses up all tokens, with
+    /// any errors of that attempt discarded. Otherwise it is parsed again from
+    /// the start as a declaration, reporting errors as usual.
+    pub fn parse_repl_line(&mut self) -> Result<ReplItem, ParseError> {
+        let start = self.token_iterator.clone();
+        let error_reporter =
+            std::mem::replace(&mut self.error_reporter, ErrorReporter::collecting());
+        let expression = self.parse_expression();
+        let is_expression =
+            self.token_iterator.peek().is_none() && !self.error_reporter.had_error();
+        self.error_reporter = error_reporter;
+        match expression {
+            Ok(expression) if is_expression => return Ok(ReplItem::Expr(expression)),
+            _ => self.token_iterator = start,
         }
+
+        let declaration = self.parse_declaration()?;
+        Ok(match declaration.kind {
+            DeclKind::Statement(statement) => ReplItem::Stmt(statement),
+            _ => ReplItem::Decl(declaration),
+        })
     }
 
-    pub fn parse_var_decl(&mut self) -> Result<VarDecl, ParseError> {
-        let var_keyword = self.consume(TokenType::Var, "Expected 'var'")?;
-        let line = var_keyword.line;
-        let column = var_keyword.column;
+    pub fn parse_declaration(&mut self) -> Result<Declaration, ParseError> {
+        // Captured up front so the declaration is positioned at its first token,
+        // whatever position its contents end up with.
+        let (line, column) = self
+            .token_iterator
+            .peek()
+            .map_or((0, 0), |token| (token.line, token.column));
+        let kind = match self.search(&[TokenType::Var, TokenType::Const]) {
+            Some(_) => {
+                let mut var_decls = self.parse_var_decl()?;
+                if var_decls.len() == 1 {
+                    DeclKind::VarDecl(var_decls.remove(0))
+                } else {
+                    DeclKind::MultiVarDecl(var_decls)
+                }
+            }
+            None => DeclKind::Statement(self.parse_statement()?),
+        };
+        Ok(Declaration { kind, line, column })
+    }
 
-        match self.token_iterator.next() {
-            Some(token) if token.token_type == TokenType::Identifier => {
-                match self.search(&[TokenType::Operator(Operator::Equal), TokenType::Semicolon]) {
-                    Some(TokenType::Operator(Operator::Equal)) => {
-                        self.token_iterator.next();
+    /// Parses a `var` or `const` statement declaring one or more comma
+    /// separated variables.
+    ///
+    /// The first declaration is positioned at the keyword and the others at
+    /// their identifiers. Constants must be initialized, as nothing could
+    /// assign them later.
+    pub fn parse_var_decl(&mut self) -> Result<Vec<VarDecl>, ParseError> {
+        let constant = self.check(TokenType::Const);
+        let var_keyword = if constant {
+            self.consume(TokenType::Const, "Expected 'const'")?
+        } else {
+            self.consume(TokenType::Var, "Expected 'var'")?
+        };
+        let mut line = var_keyword.line;
+        let mut column = var_keyword.column;
+        let mut var_decls = vec![];
 
-                        let expression = self.parse_assignment()?;
-                        self.consume(
-                            TokenType::Semicolon,
-                            "Expect ';' after variable declaration.",
-                        )?;
-                        Ok(VarDecl {
-                            identifier: token.lexeme.clone(),
-                            initializer: Some(expression),
-                            line,
-                            column,
-                        })
-                    }
-                    Some(TokenType::Semicolon) => {
-                        self.token_iterator.next();
-                        Ok(VarDecl {
-                            identifier: token.lexeme.clone(),
-                            initializer: None,
-                            line,
-                            column,
-                        })
-                    }
-                    _ => Err(ParseError::UnexpectedToken),
+        loop {
+            let identifier = self
+                .consume(TokenType::Identifier, "Expect variable name.")?
+                .symbol();
+            let initializer = match self.search(&[TokenType::Operator(Operator::Equal)]) {
+                Some(_) => {
+                    self.token_iterator.next(); // Consume the '=' token
+                    Some(self.parse_assignment()?)
+                }
+                None if constant => {
+                    self.consume(
+                        TokenType::Operator(Operator::Equal),
+                        "Expect '=' after constant name.",
+                    )?;
+                    None
                 }
+                None => None,
+            };
+            let redeclared = self
+                .scopes
+                .last_mut()
+                .is_some_and(|scope| !scope.insert(identifier));
+            if redeclared {
+                self.error_reporter.error(
+                    line,
+                    column,
+                    &format!(
+                        "Variable '{}' is already declared in this scope.",
+                        identifier
+                    ),
+                );
+            }
+            var_decls.push(VarDecl {
+                identifier,
+                initializer,
+                constant,
+                line,
+                column,
+            });
+
+            if !self.check(TokenType::Comma) {
+                break;
+            }
+            self.token_iterator.next(); // Consume the ',' token
+            if let Some(token) = self.token_iterator.peek() {
+                line = token.line;
+                column = token.column;
             }
-            Some(_) => Err(ParseError::UnexpectedToken),
-            _ => Err(ParseError::UnexpectedEOF),
         }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(var_decls)
     }
+
+    /// Parses a statement.
+    ///
+    /// A `{` here always opens a block statement, even when a map literal
+    /// could follow; braces in expression position are told apart by
+    /// `parse_brace_expression`.
     pub fn parse_statement(&mut self) -> Result<Statement, ParseError> {
-        let search_tokens = vec![
-            TokenType::Print,
-            TokenType::LeftBrace,
-            TokenType::While,
-            TokenType::For,
-            TokenType::If,
-        ];
-        match self.search(&search_tokens) {
+        if self.at_labeled_statement() {
+            return self.parse_labeled_statement();
+        }
+        match self.search(&STATEMENT_STARTS) {
             Some(TokenType::Print) => self.parse_print_statement(),
             Some(TokenType::LeftBrace) => self.parse_block(),
             Some(TokenType::If) => self.parse_if_statement(),
-            Some(TokenType::While) => self.parse_while_statement(),
-            Some(TokenType::For) => self.parse_for_statement(),
+            Some(TokenType::While) => self.parse_while_statement(None),
+            Some(TokenType::For) => self.parse_for_statement(None),
+            Some(TokenType::Switch) => self.parse_switch_statement(),
+            Some(TokenType::Break) => self.parse_jump_statement(TokenType::Break),
+            Some(TokenType::Continue) => self.parse_jump_statement(TokenType::Continue),
+            Some(TokenType::Fallthrough) => self.misplaced_fallthrough(),
             _ => self.parse_expression_statement(),
         }
     }
@@ -122,36 +300,69 @@ impl<'a> Parser<'a> {
         let print_keyword = self.consume(TokenType::Print, "Expected 'print'")?;
         let line = print_keyword.line;
         let column = print_keyword.column;
-        let expression = self.parse_expression()?;
+        // The commas separate arguments here rather than forming a sequence.
+        let mut expressions = vec![self.parse_assignment()?];
+        while self.check(TokenType::Comma) {
+            self.token_iterator.next(); // Consume the ',' token
+            expressions.push(self.parse_assignment()?);
+        }
         self.consume(TokenType::Semicolon, "Expected ';' after Expression.")?;
         Ok(Statement {
-            kind: StmtKind::PrintStmt {
-                expression: Box::new(expression),
-            },
+            kind: StmtKind::PrintStmt { expressions },
             line,
             column,
         })
     }
 
-    fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+    /// Returns whether the next tokens are a label, `identifier:`, starting a
+    /// labeled statement.
+    fn at_labeled_statement(&mut self) -> bool {
+        self.check(TokenType::Identifier) && self.peek_second_is(TokenType::Colon)
+    }
+
+    /// Parses `label: while ...` or `label: for ...`.
+    ///
+    /// The label can be targeted by `break` and `continue` anywhere in the
+    /// loop's body, and only there.
+    fn parse_labeled_statement(&mut self) -> Result<Statement, ParseError> {
+        let label = self
+            .consume(TokenType::Identifier, "Expected a label")?
+            .symbol();
+        self.consume(TokenType::Colon, "Expected ':' after label")?;
+        if self.search(&[TokenType::While, TokenType::For]).is_none() {
+            // Reports the error, as the next token is known not to be `while`.
+            self.consume(TokenType::While, "Expected a loop after label.")?;
+        }
+        self.labels.push(label);
+        let statement = if self.check(TokenType::For) {
+            self.parse_for_statement(Some(label))
+        } else {
+            self.parse_while_statement(Some(label))
+        };
+        self.labels.pop();
+        statement
+    }
+
+    fn parse_while_statement(&mut self, label: Option<Symbol>) -> Result<Statement, ParseError> {
         let while_keyword = self.consume(TokenType::While, "Expected 'while'")?;
         let line = while_keyword.line;
         let column = while_keyword.column;
         self.consume(TokenType::LeftParen, "Expected '(' after while")?;
         let condition = self.parse_expression()?;
         self.consume(TokenType::RightParen, "Expected ')' after while condition")?;
-        let do_stmt = self.parse_statement()?;
+        let do_stmt = self.parse_loop_body()?;
         Ok(Statement {
             kind: StmtKind::WhileStmt {
                 condition: Box::new(condition),
                 do_stmt: Box::new(do_stmt),
+                label,
             },
             line,
             column,
         })
     }
 
-    fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+    fn parse_for_statement(&mut self, label: Option<Symbol>) -> Result<Statement, ParseError> {
         let for_keyword = self.consume(TokenType::For, "Expected 'for'")?;
         let line = for_keyword.line;
         let column = for_keyword.column;
@@ -160,7 +371,8 @@ impl<'a> Parser<'a> {
             self.token_iterator.next(); // Consume the semicolon
             None
         } else {
-            Some(Box::new(self.parse_declaration()?))
+            // The initializer gets a scope of its own, around the body's.
+            Some(Box::new(self.in_scope(Self::parse_declaration)?))
         };
 
         let condition = if self.check(TokenType::Semicolon) {
@@ -179,7 +391,7 @@ impl<'a> Parser<'a> {
             Some(Box::new(self.parse_expression()?))
         };
         self.consume(TokenType::RightParen, "Expected ')' after for clauses")?;
-        let body = Box::new(self.parse_statement()?);
+        let body = Box::new(self.parse_loop_body()?);
 
         Ok(Statement {
             kind: StmtKind::ForStmt {
@@ -187,12 +399,21 @@ impl<'a> Parser<'a> {
                 condition,
                 update,
                 body,
+                label,
             },
             line,
             column,
         })
     }
 
+    /// Parses the body of a loop, where `break` and `continue` are allowed.
+    fn parse_loop_body(&mut self) -> Result<Statement, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
     fn parse_if_statement(&mut self) -> Result<Statement, ParseError> {
         let if_keyword = self.consume(TokenType::If, "Expected 'if'")?;
         let line = if_keyword.line;
@@ -217,6 +438,189 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses `break;` or `continue;`, optionally followed by the label of
+    /// an enclosing loop.
+    ///
+    /// An unlabeled `break` may also leave an enclosing switch. A label that
+    /// no enclosing loop carries is reported, but parsing goes on.
+    fn parse_jump_statement(&mut self, keyword: TokenType) -> Result<Statement, ParseError> {
+        let missing_semicolon = format!("Expected ';' after '{}'.", keyword);
+        let keyword_token = self.consume(keyword.clone(), "Expected 'break' or 'continue'")?;
+        let (line, column, length) = (
+            keyword_token.line,
+            keyword_token.column,
+            keyword_token.length,
+        );
+        if keyword == TokenType::Break && self.loop_depth == 0 && self.switch_depth == 0 {
+            self.error_reporter.error_spanning(
+                line,
+                column,
+                length,
+                "Cannot use 'break' outside of a loop or switch.",
+            );
+        } else if keyword == TokenType::Continue && self.loop_depth == 0 {
+            self.error_reporter.error_spanning(
+                line,
+                column,
+                length,
+                "Cannot use 'continue' outside of a loop.",
+            );
+        }
+        let label = match self.search(&[TokenType::Identifier]) {
+            Some(_) => {
+                let token = self.token_iterator.next().unwrap();
+                let label = token.symbol();
+                if !self.labels.contains(&label) {
+                    self.error_reporter.error_spanning(
+                        token.line,
+                        token.column,
+                        token.length,
+                        &format!("Undefined label '{}'.", token.lexeme),
+                    );
+                }
+                Some(label)
+            }
+            None => None,
+        };
+        self.consume(TokenType::Semicolon, &missing_semicolon)?;
+        let kind = match keyword {
+            TokenType::Break => StmtKind::Break { label },
+            _ => StmtKind::Continue { label },
+        };
+        Ok(Statement { kind, line, column })
+    }
+
+    /// Parses `switch (expr) { case value: ... default: ... }`.
+    ///
+    /// The declarations following a `case` or `default` label belong to it
+    /// until the next label or the closing brace. Cases do not fall through
+    /// unless they end with `fallthrough;`, which the default case, run after
+    /// every other case, cannot do.
+    fn parse_switch_statement(&mut self) -> Result<Statement, ParseError> {
+        let switch_keyword = self.consume(TokenType::Switch, "Expected 'switch'")?;
+        let line = switch_keyword.line;
+        let column = switch_keyword.column;
+        self.consume(TokenType::LeftParen, "Expected '(' after switch")?;
+        let discriminant = self.parse_expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after switch value")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' before switch cases")?;
+
+        self.switch_depth += 1;
+        let arms = self.parse_switch_arms();
+        self.switch_depth -= 1;
+        let (cases, default) = arms?;
+        self.consume(
+            TokenType::RightBrace,
+            "Expected 'case', 'default' or '}' in switch",
+        )?;
+
+        Ok(Statement {
+            kind: StmtKind::Switch {
+                discriminant: Box::new(discriminant),
+                cases,
+                default,
+            },
+            line,
+            column,
+        })
+    }
+
+    /// Parses the `case` and `default` arms of a switch, up to its closing brace.
+    fn parse_switch_arms(
+        &mut self,
+    ) -> Result<(Vec<SwitchCase>, Option<Vec<Declaration>>), ParseError> {
+        let mut cases = Vec::new();
+        let mut default = None;
+        while let Some(label) = self.search(&[TokenType::Case, TokenType::Default]) {
+            let label_token = self
+                .token_iterator
+                .next()
+                .ok_or(ParseError::UnexpectedEOF)?;
+            let (label_line, label_column) = (label_token.line, label_token.column);
+            let value = match label {
+                TokenType::Case => Some(self.parse_expression()?),
+                _ => None,
+            };
+            self.consume(TokenType::Colon, "Expected ':' after case label")?;
+            let (body, fallthrough) = self.in_scope(Self::parse_case_body)?;
+            match value {
+                Some(value) => cases.push(SwitchCase {
+                    value,
+                    body,
+                    fallthrough: fallthrough.is_some(),
+                }),
+                None if default.is_none() => {
+                    if let Some((line, column)) = fallthrough {
+                        self.error_reporter.error(
+                            line,
+                            column,
+                            "Cannot fall through out of the default case.",
+                        );
+                    }
+                    default = Some(body);
+                }
+                None => self.error_reporter.error(
+                    label_line,
+                    label_column,
+                    "A switch statement can only have one default case.",
+                ),
+            }
+        }
+        Ok((cases, default))
+    }
+
+    /// Parses the declarations of a switch case up to the next label or `}`.
+    ///
+    /// Also returns the position of a `fallthrough;` ending the case, if there
+    /// is one. A `fallthrough` followed by more declarations is reported.
+    fn parse_case_body(&mut self) -> Result<CaseBody, ParseError> {
+        let mut body = Vec::new();
+        let mut fallthrough = None;
+        while self
+            .search(&[TokenType::Case, TokenType::Default, TokenType::RightBrace])
+            .is_none()
+            && self.token_iterator.peek().is_some()
+        {
+            if let Some((line, column)) = fallthrough.take() {
+                self.error_reporter.error(
+                    line,
+                    column,
+                    "'fallthrough' must be the last statement of a case.",
+                );
+            }
+            if self.check(TokenType::Fallthrough) {
+                let keyword = self.consume(TokenType::Fallthrough, "Expected 'fallthrough'")?;
+                fallthrough = Some((keyword.line, keyword.column));
+                self.consume(TokenType::Semicolon, "Expected ';' after 'fallthrough'.")?;
+                continue;
+            }
+            body.push(self.parse_declaration()?);
+        }
+        Ok((body, fallthrough))
+    }
+
+    /// Parses a `fallthrough;` that does not end a switch case, reporting it.
+    ///
+    /// Parsing goes on with an empty block standing in for the statement.
+    fn misplaced_fallthrough(&mut self) -> Result<Statement, ParseError> {
+        let keyword = self.consume(TokenType::Fallthrough, "Expected 'fallthrough'")?;
+        let (line, column, length) = (keyword.line, keyword.column, keyword.length);
+        self.error_reporter.error_spanning(
+            line,
+            column,
+            length,
+            "Cannot use 'fallthrough' outside of a switch case.",
+        );
+        self.consume(TokenType::Semicolon, "Expected ';' after 'fallthrough'.")?;
+        Ok(Statement {
+            kind: StmtKind::Block {
+                declarations: vec![],
+            },
+            line,
+            column,
+        })
+    }
+
     fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
         let expression = self.parse_expression()?;
         let line = expression.line;
@@ -231,15 +635,27 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Runs `parse` inside a new local scope, where a variable can only be
+    /// declared once.
+    fn in_scope<T>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashSet::new());
+        let result = parse(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Parses a block statement, `{` declarations `}`.
     fn parse_block(&mut self) -> Result<Statement, ParseError> {
-        let brace = self.consume(TokenType::LeftBrace, "Expected '('")?;
+        let brace = self.consume(TokenType::LeftBrace, "Expected '{'")?;
         let line = brace.line;
         let column = brace.column;
-        let mut declarations = Vec::new();
-
-        while !self.check(TokenType::RightBrace) && self.token_iterator.peek().is_some() {
-            declarations.push(self.parse_declaration()?);
-        }
+        let declarations = self.in_scope(|parser| {
+            let mut declarations = Vec::new();
+            while !parser.check(TokenType::RightBrace) && parser.token_iterator.peek().is_some() {
+                declarations.push(parser.parse_declaration()?);
+            }
+            Ok(declarations)
+        })?;
 
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
 
@@ -251,11 +667,41 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_expression(&mut self) -> Result<Expression, ParseError> {
-        self.parse_assignment()
+        self.parse_sequence()
     }
 
+    /// Parses comma separated expressions at the lowest precedence.
+    ///
+    /// Places where a comma delimits something else (such as the elements of a
+    /// list) should call `parse_assignment` directly instead.
+    fn parse_sequence(&mut self) -> Result<Expression, ParseError> {
+        let first = self.parse_assignment()?;
+        if !self.check(TokenType::Comma) {
+            return Ok(first);
+        }
+        let line = first.line;
+        let column = first.column;
+        let mut expressions = vec![first];
+        while self.check(TokenType::Comma) {
+            self.token_iterator.next(); // Consume the ',' token
+            expressions.push(self.parse_assignment()?);
+        }
+        Ok(Expression {
+            kind: ExprKind::Sequence { expressions },
+            line,
+            column,
+        })
+    }
+
+    /// Parses an assignment, or the expression it would assign to.
+    ///
+    /// A target that cannot be assigned to is reported where it starts, and
+    /// parsing goes on with the assigned value in place of the assignment.
     fn parse_assignment(&mut self) -> Result<Expression, ParseError> {
-        let expr = self.logical()?;
+        if self.check(TokenType::Global) {
+            return self.parse_global_assignment();
+        }
+        let expr = self.nil_coalescing()?;
 
         if let Some(TokenType::Operator(Operator::Equal)) =
             self.search(&[TokenType::Operator(Operator::Equal)])
@@ -263,30 +709,83 @@ impl<'a> Parser<'a> {
             self.token_iterator.next(); // Consume the '=' token
             let value = self.parse_assignment()?;
 
-            if let ExprKind::Var { identifier } = expr.kind {
-                return Ok(self.create_expression(ExprKind::Assignment {
-                    identifier,
-                    value: Box::new(value),
-                }));
+            if !expr.is_assignable_target() {
+                self.error_reporter
+                    .error(expr.line, expr.column, "Invalid assignment target.");
+                return Ok(value);
             }
 
-            self.error_reporter
-                .error(expr.line, expr.column, "Invalid assignment target.");
+            let kind = match expr.kind {
+                ExprKind::Var { identifier } => ExprKind::Assignment {
+                    identifier,
+                    value: Box::new(value),
+                },
+                ExprKind::Index { object, index } => ExprKind::IndexAssignment {
+                    object,
+                    index,
+                    value: Box::new(value),
+                },
+                _ => unreachable!("every assignable target is handled above"),
+            };
+            return Ok(Expression {
+                kind,
+                line: expr.line,
+                column: expr.column,
+            });
         }
 
         Ok(expr)
     }
 
+    /// Parses `global identifier = value`, which assigns to the global binding
+    /// even when a local variable shadows it.
+    fn parse_global_assignment(&mut self) -> Result<Expression, ParseError> {
+        let global_keyword = self.consume(TokenType::Global, "Expected 'global'")?;
+        let line = global_keyword.line;
+        let column = global_keyword.column;
+        let identifier = self
+            .consume(
+                TokenType::Identifier,
+                "Expect variable name after 'global'.",
+            )?
+            .symbol();
+        self.consume(
+            TokenType::Operator(Operator::Equal),
+            "Expect '=' after global variable name.",
+        )?;
+        let value = self.parse_assignment()?;
+        Ok(Expression {
+            kind: ExprKind::GlobalAssignment {
+                identifier,
+                value: Box::new(value),
+            },
+            line,
+            column,
+        })
+    }
+
+    /// Parses `a ?? b`, which binds just tighter than assignment and more
+    /// loosely than `and` and `or`.
+    fn nil_coalescing(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.logical()?;
+        while self.check(TokenType::Operator(Operator::QuestionQuestion)) {
+            let Some(operator) = self.token_iterator.next() else {
+                return Err(ParseError::UnexpectedEOF);
+            };
+            let right = self.logical()?;
+            expr = Self::logical_expression(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
     fn logical(&mut self) -> Result<Expression, ParseError> {
         let mut expr = self.equality()?;
-        while let Some(token_type) = self.search(&[TokenType::And, TokenType::Or]) {
-            self.token_iterator.next();
+        while self.search(&[TokenType::And, TokenType::Or]).is_some() {
+            let Some(operator) = self.token_iterator.next() else {
+                return Err(ParseError::UnexpectedEOF);
+            };
             let right = self.equality()?;
-            expr = self.create_expression(ExprKind::Logical {
-                left: Box::new(expr),
-                logic_op: token_type,
-                right: Box::new(right),
-            });
+            expr = Self::logical_expression(expr, operator, right);
         }
         Ok(expr)
     }
@@ -295,7 +794,6 @@ impl<'a> Parser<'a> {
     ///
     /// This method is used by various parsing methods to handle binary operations
     /// at different precedence levels.
-
     fn binary_op<F>(
         &mut self,
         mut left: Expression,
@@ -308,13 +806,18 @@ impl<'a> Parser<'a> {
         while let Some(TokenType::Operator(op)) = self.search(operators) {
             if let Some(token) = self.token_iterator.next() {
                 let right = next_precedence(self)?;
-                left = self.create_expression(ExprKind::Binary {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
-                });
-                left.line = token.line;
-                left.column = token.column;
+                let (line, column) = (left.line, left.column);
+                left = Expression {
+                    kind: ExprKind::Binary {
+                        left: Box::new(left),
+                        operator: op,
+                        op_line: token.line,
+                        op_column: token.column,
+                        right: Box::new(right),
+                    },
+                    line,
+                    column,
+                };
             } else {
                 return Err(ParseError::UnexpectedToken);
             }
@@ -323,19 +826,49 @@ impl<'a> Parser<'a> {
     }
 
     fn equality(&mut self) -> Result<Expression, ParseError> {
-        let left = self.comparison()?;
+        let left = self.bitwise_or()?;
         self.binary_op(
             left,
             &[
                 TokenType::Operator(Operator::BangEqual),
                 TokenType::Operator(Operator::EqualEqual),
             ],
+            Self::bitwise_or,
+        )
+    }
+
+    /// The bitwise operators bind tighter than equality but looser than
+    /// comparison, from loosest to tightest: `|`, `^`, `&`.
+    /// So `a & b == c` parses as `(a & b) == c`.
+    fn bitwise_or(&mut self) -> Result<Expression, ParseError> {
+        let left = self.bitwise_xor()?;
+        self.binary_op(
+            left,
+            &[TokenType::Operator(Operator::Pipe)],
+            Self::bitwise_xor,
+        )
+    }
+
+    fn bitwise_xor(&mut self) -> Result<Expression, ParseError> {
+        let left = self.bitwise_and()?;
+        self.binary_op(
+            left,
+            &[TokenType::Operator(Operator::Caret)],
+            Self::bitwise_and,
+        )
+    }
+
+    fn bitwise_and(&mut self) -> Result<Expression, ParseError> {
+        let left = self.comparison()?;
+        self.binary_op(
+            left,
+            &[TokenType::Operator(Operator::Amp)],
             Self::comparison,
         )
     }
 
     fn comparison(&mut self) -> Result<Expression, ParseError> {
-        let left = self.term()?;
+        let left = self.shift()?;
         self.binary_op(
             left,
             &[
@@ -344,6 +877,20 @@ impl<'a> Parser<'a> {
                 TokenType::Operator(Operator::Less),
                 TokenType::Operator(Operator::LessEqual),
             ],
+            Self::shift,
+        )
+    }
+
+    /// Shifts bind between comparison and addition, as in C,
+    /// so `1 << 2 + 1` parses as `1 << (2 + 1)`.
+    fn shift(&mut self) -> Result<Expression, ParseError> {
+        let left = self.term()?;
+        self.binary_op(
+            left,
+            &[
+                TokenType::Operator(Operator::Shl),
+                TokenType::Operator(Operator::Shr),
+            ],
             Self::term,
         )
     }
@@ -372,45 +919,184 @@ impl<'a> Parser<'a> {
         )
     }
 
+    /// Parses prefix operators.
+    ///
+    /// `typeof` is parsed here too, so it binds as tightly as `-` and `!`:
+    /// `typeof 1 + 2` is `(typeof 1) + 2`.
     fn unary(&mut self) -> Result<Expression, ParseError> {
+        if self.check(TokenType::TypeOf) {
+            let (line, column) = match self.token_iterator.next() {
+                Some(token) => (token.line, token.column),
+                None => return Err(ParseError::UnexpectedEOF),
+            };
+            let operand = self.unary()?;
+            return Ok(Expression {
+                kind: ExprKind::TypeOf {
+                    operand: Box::new(operand),
+                },
+                line,
+                column,
+            });
+        }
         let search_types = [
             TokenType::Operator(Operator::Bang),
             TokenType::Operator(Operator::Minus),
+            TokenType::Operator(Operator::PlusPlus),
+            TokenType::Operator(Operator::MinusMinus),
         ];
         if let Some(TokenType::Operator(op)) = self.search(&search_types) {
             match op {
+                Operator::PlusPlus | Operator::MinusMinus => {
+                    let (line, column) = match self.token_iterator.next() {
+                        Some(token) => (token.line, token.column),
+                        None => return Err(ParseError::UnexpectedEOF),
+                    };
+                    let operand = self.unary()?;
+                    self.inc_dec(operand, op, true, line, column)
+                }
                 Operator::Bang | Operator::Minus => {
-                    self.token_iterator.next(); // Consume the token
+                    let (line, column) = match self.token_iterator.next() {
+                        Some(token) => (token.line, token.column),
+                        None => return Err(ParseError::UnexpectedEOF),
+                    };
                     let right = self.unary()?;
-                    Ok(self.create_expression(ExprKind::Unary {
-                        operator: op,
-                        right: Box::new(right),
-                    }))
+                    Ok(Expression {
+                        kind: ExprKind::Unary {
+                            operator: op,
+                            right: Box::new(right),
+                        },
+                        line,
+                        column,
+                    })
                 }
                 _ => {
                     let token = self.token_iterator.peek().unwrap();
-                    self.error_reporter.error(
+                    self.error_reporter.error_spanning(
                         token.line,
                         token.column,
+                        token.length,
                         "Unexpected operator in unary expression",
                     );
                     Err(ParseError::UnexpectedToken)
                 }
             }
         } else {
-            self.primary()
+            self.power()
+        }
+    }
+
+    /// Parses exponentiation, which binds tighter than unary operators and is
+    /// right-associative.
+    ///
+    /// As in Python, `-2 ** 2` is `-(2 ** 2)`, and the exponent is parsed as a
+    /// unary expression so that `2 ** -1` is allowed. This recurses back into
+    /// `power`, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    fn power(&mut self) -> Result<Expression, ParseError> {
+        let base = self.postfix()?;
+        if self
+            .search(&[TokenType::Operator(Operator::StarStar)])
+            .is_none()
+        {
+            return Ok(base);
+        }
+        let (op_line, op_column) = match self.token_iterator.next() {
+            Some(token) => (token.line, token.column),
+            None => return Err(ParseError::UnexpectedEOF),
+        };
+        let exponent = self.unary()?;
+        let (line, column) = (base.line, base.column);
+        Ok(Expression {
+            kind: ExprKind::Binary {
+                left: Box::new(base),
+                operator: Operator::StarStar,
+                op_line,
+                op_column,
+                right: Box::new(exponent),
+            },
+            line,
+            column,
+        })
+    }
+    /// Parses a postfix increment or decrement such as `i++`.
+    fn postfix(&mut self) -> Result<Expression, ParseError> {
+        let expr = self.subscript()?;
+        let search_types = [
+            TokenType::Operator(Operator::PlusPlus),
+            TokenType::Operator(Operator::MinusMinus),
+        ];
+        if let Some(TokenType::Operator(op)) = self.search(&search_types) {
+            let (line, column) = match self.token_iterator.next() {
+                Some(token) => (token.line, token.column),
+                None => return Err(ParseError::UnexpectedEOF),
+            };
+            return self.inc_dec(expr, op, false, line, column);
+        }
+        Ok(expr)
+    }
+
+    /// Parses a primary expression followed by any number of `[index]` reads,
+    /// so `m["a"]["b"]` indexes the result of `m["a"]`.
+    fn subscript(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.primary()?;
+        while self.check(TokenType::LeftBracket) {
+            self.token_iterator.next();
+            let index = self.parse_expression()?;
+            self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+            let (line, column) = (expr.line, expr.column);
+            expr = Expression {
+                kind: ExprKind::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                },
+                line,
+                column,
+            };
+        }
+        Ok(expr)
+    }
+
+    /// Builds an increment or decrement of `operand`, which must be a variable.
+    fn inc_dec(
+        &mut self,
+        operand: Expression,
+        operator: Operator,
+        prefix: bool,
+        line: usize,
+        column: usize,
+    ) -> Result<Expression, ParseError> {
+        match operand.kind {
+            ExprKind::Var { identifier } => Ok(Expression {
+                kind: ExprKind::IncDec {
+                    identifier,
+                    operator,
+                    prefix,
+                },
+                line,
+                column,
+            }),
+            _ => {
+                self.error_reporter.error(
+                    operand.line,
+                    operand.column,
+                    &format!("Invalid {} target, expected a variable.", operator),
+                );
+                Err(ParseError::UnexpectedToken)
+            }
         }
     }
+
     fn primary(&mut self) -> Result<Expression, ParseError> {
         let token = self.token_iterator.next().ok_or_else(|| {
             self.error_reporter.error(0, 0, "Unexpected end of input");
-            ParseError::UnexpectedToken
+            ParseError::UnexpectedEOF
         })?;
 
         match token.token_type {
             TokenType::False
             | TokenType::True
             | TokenType::Nil
+            | TokenType::Infinity
+            | TokenType::NaN
             | TokenType::Number
             | TokenType::String => {
                 let value = token.literal.clone().ok_or_else(|| {
@@ -418,28 +1104,165 @@ impl<'a> Parser<'a> {
                         .error(token.line, token.column, "Expected literal value");
                     ParseError::UnexpectedToken
                 })?;
-                Ok(self.create_expression(ExprKind::Lit { value }))
+                Ok(Expression {
+                    kind: ExprKind::Lit { value },
+                    line: token.line,
+                    column: token.column,
+                })
             }
-            TokenType::Identifier => Ok(self.create_expression(ExprKind::Var {
-                identifier: token.lexeme.clone(),
-            })),
+            TokenType::Identifier => Ok(Expression {
+                kind: ExprKind::Var {
+                    identifier: token.symbol(),
+                },
+                line: token.line,
+                column: token.column,
+            }),
             TokenType::LeftParen => {
                 let expression = self.parse_expression()?;
                 self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
-                Ok(self.create_expression(ExprKind::Grouping {
-                    expression: Box::new(expression),
-                }))
+                Ok(Expression {
+                    kind: ExprKind::Grouping {
+                        expression: Box::new(expression),
+                    },
+                    line: token.line,
+                    column: token.column,
+                })
             }
+            TokenType::LeftBrace => self.parse_brace_expression(token.line, token.column),
             _ => {
-                self.error_reporter.error(
+                self.error_reporter.error_spanning(
                     token.line,
                     token.column,
-                    &format!("Unexpected token: {:?}", token.token_type),
+                    token.length,
+                    &format!(
+                        "Unexpected token: {:?} near: `{}`",
+                        token.token_type,
+                        self.context_before(token)
+                    ),
                 );
                 Err(ParseError::UnexpectedToken)
             }
         }
     }
+    /// Parses the rest of an expression opening with `{`, which is either a
+    /// map literal or a block expression.
+    ///
+    /// This is the one place braces in expression position are told apart:
+    /// - `{}` is an empty map, as an empty block expression would only ever
+    ///   be nil.
+    /// - `{` followed by a literal, such as a string or number, and `:` is a
+    ///   map literal.
+    /// - Anything else is a block expression.
+    ///
+    /// At statement position `{` is never an expression, see `parse_statement`.
+    fn parse_brace_expression(
+        &mut self,
+        line: usize,
+        column: usize,
+    ) -> Result<Expression, ParseError> {
+        let starts_with_key = self.search(&LITERAL_TOKENS).is_some();
+        if self.check(TokenType::RightBrace)
+            || (starts_with_key && self.peek_second_is(TokenType::Colon))
+        {
+            self.parse_map_literal(line, column)
+        } else {
+            self.parse_block_expression(line, column)
+        }
+    }
+
+    /// Parses the rest of a map literal after its opening `{`.
+    ///
+    /// Entries are `key: value` pairs separated by commas, with an optional
+    /// trailing comma. Keys may be any expression; only the first one has to be
+    /// a literal, as that is what tells the map from a block.
+    fn parse_map_literal(&mut self, line: usize, column: usize) -> Result<Expression, ParseError> {
+        let mut entries = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let key = self.parse_assignment()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value = self.parse_assignment()?;
+            entries.push((key, value));
+            if !self.check(TokenType::Comma) {
+                break;
+            }
+            self.token_iterator.next();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expression {
+            kind: ExprKind::MapLit { entries },
+            line,
+            column,
+        })
+    }
+
+    /// Parses the rest of a block expression after its opening `{`.
+    ///
+    /// Declarations and statements are parsed as in a block statement, except
+    /// that an expression followed directly by `}` instead of `;` becomes the
+    /// block's value.
+    ///
+    /// Control cannot leave a block expression through `break` or `continue`,
+    /// so the loops and switches around it are out of reach inside it.
+    fn parse_block_expression(
+        &mut self,
+        line: usize,
+        column: usize,
+    ) -> Result<Expression, ParseError> {
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let switch_depth = std::mem::take(&mut self.switch_depth);
+        let labels = std::mem::take(&mut self.labels);
+        let block = self.in_scope(|parser| parser.parse_block_expression_body(line, column));
+        self.loop_depth = loop_depth;
+        self.switch_depth = switch_depth;
+        self.labels = labels;
+        block
+    }
+
+    fn parse_block_expression_body(
+        &mut self,
+        line: usize,
+        column: usize,
+    ) -> Result<Expression, ParseError> {
+        let mut declarations = Vec::new();
+        let mut tail = None;
+
+        while !self.check(TokenType::RightBrace) && self.token_iterator.peek().is_some() {
+            if self.check(TokenType::Var)
+                || self.check(TokenType::Const)
+                || self.search(&STATEMENT_STARTS).is_some()
+                || self.at_labeled_statement()
+            {
+                declarations.push(self.parse_declaration()?);
+                continue;
+            }
+            let expression = self.parse_expression()?;
+            if self.check(TokenType::RightBrace) {
+                tail = Some(Box::new(expression));
+                break;
+            }
+            self.consume(TokenType::Semicolon, "Expect ';' or '}' after expression.")?;
+            let (expr_line, expr_column) = (expression.line, expression.column);
+            declarations.push(Declaration {
+                kind: DeclKind::Statement(Statement {
+                    kind: StmtKind::ExprStmt {
+                        expression: Box::new(expression),
+                    },
+                    line: expr_line,
+                    column: expr_column,
+                }),
+                line: expr_line,
+                column: expr_column,
+            });
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(Expression {
+            kind: ExprKind::BlockExpr { declarations, tail },
+            line,
+            column,
+        })
+    }
+
     fn consume(
         &mut self,
         token_type: TokenType,
@@ -449,8 +1272,12 @@ impl<'a> Parser<'a> {
             Ok(self.token_iterator.next().unwrap())
         } else {
             if let Some(token) = self.token_iterator.peek() {
-                self.error_reporter
-                    .error(token.line, token.column, error_message);
+                self.error_reporter.error_spanning(
+                    token.line,
+                    token.column,
+                    token.length,
+                    error_message,
+                );
             } else {
                 self.error_reporter.error(0, 0, "Unexpected end of input");
                 return Err(ParseError::MissingToken);
@@ -459,20 +1286,47 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Creates an Expression with the current token's line and column information.    
-    fn create_expression(&mut self, kind: ExprKind) -> Expression {
-        let (line, column) = if let Some(token) = self.token_iterator.peek() {
-            (token.line, token.column)
-        } else {
-            (0, 0)
-        };
-        Expression { kind, line, column }
+    /// Builds a logical expression, positioned where its left operand starts.
+    fn logical_expression(left: Expression, operator: &Token, right: Expression) -> Expression {
+        let (line, column) = (left.line, left.column);
+        Expression {
+            kind: ExprKind::Logical {
+                left: Box::new(left),
+                logic_op: operator.token_type.clone(),
+                op_line: operator.line,
+                op_column: operator.column,
+                right: Box::new(right),
+            },
+            line,
+            column,
+        }
+    }
+
+    /// Reconstructs the source of `token`'s line up to and including it.
+    fn context_before(&self, token: &Token) -> String {
+        let on_line = |t: &Token| t.line == token.line;
+        let start = self.tokens.iter().position(on_line).unwrap_or(0);
+        let end = self
+            .tokens
+            .iter()
+            .rposition(|t| on_line(t) && t.column <= token.column)
+            .map_or(start, |index| index + 1);
+        tokens_to_source(&self.tokens[start..end])
+    }
+
+    /// Checks the type of the token after the next one, for the few places
+    /// where one token of lookahead isn't enough.
+    fn peek_second_is(&self, token_type: TokenType) -> bool {
+        self.token_iterator
+            .clone()
+            .nth(1)
+            .is_some_and(|t| t.token_type == token_type)
     }
 
     fn check(&mut self, token_type: TokenType) -> bool {
         self.token_iterator
             .peek()
-            .map_or(false, |t| t.token_type == token_type)
+            .is_some_and(|t| t.token_type == token_type)
     }
 
     fn search(&mut self, search_types: &[TokenType]) -> Option<TokenType> {
@@ -489,7 +1343,6 @@ impl<'a> Parser<'a> {
     fn synchronize(&mut self) -> Result<(), ParseError> {
         while let Some(token) = self.token_iterator.next() {
             if token.token_type == TokenType::Semicolon {
-                self.token_iterator.next();
                 return Ok(());
             }
 
@@ -498,9 +1351,11 @@ impl<'a> Parser<'a> {
                     TokenType::Class
                     | TokenType::Fun
                     | TokenType::Var
+                    | TokenType::Const
                     | TokenType::For
                     | TokenType::If
                     | TokenType::While
+                    | TokenType::Switch
                     | TokenType::Print
                     | TokenType::LeftBrace
                     | TokenType::Return => return Ok(()),
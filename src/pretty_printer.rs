@@ -4,71 +4,114 @@
 //! Lox programs, declarations, statements, and expressions into a readable string format
 //! for debugging or display purposes.
 use crate::ast::{
-    DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind, VarDecl,
+    DeclKind, Declaration, ExprKind, Expression, Program, Statement, StmtKind, SwitchCase, VarDecl,
+    Visitor,
 };
-use crate::token::{Literal, Operator, TokenType};
+use crate::interner::Symbol;
+use crate::token::{format_number, Literal, Operator, TokenType};
 
-pub struct PrettyPrinter;
+/// Prints the AST as a `Visitor` producing the text of every node.
+#[derive(Default)]
+pub struct PrettyPrinter {
+    /// Annotate every expression with the `@line:column` it was parsed at.
+    positions: bool,
+    /// Print grouped expressions without their `(group ...)` wrapper.
+    omit_groupings: bool,
+}
 
 impl PrettyPrinter {
     pub fn new() -> Self {
-        PrettyPrinter
+        PrettyPrinter {
+            positions: false,
+            omit_groupings: false,
+        }
+    }
+
+    /// Annotates every printed expression with its source position, as in
+    /// `(+ @1:7 1 2)`, to check the positions the parser records.
+    ///
+    /// Nodes printed with their operator first carry the annotation right
+    /// after it, variables and literals such as `x@1:7` or `"a b"@1:7`
+    /// directly after them, and all others, such as assignments, logical
+    /// expressions and map literals, in front of them.
+    pub fn with_positions(mut self, positions: bool) -> Self {
+        self.positions = positions;
+        self
     }
 
-    pub fn print_program(&self, program: &Program) -> String {
+    pub fn print_program(&mut self, program: &Program) -> String {
         program
             .iter()
-            .map(|decl| self.print_declaration(decl))
+            .map(|decl| self.visit_decl(decl))
             .collect::<Vec<_>>()
             .join("\n")
     }
 
-    pub fn print_declaration(&self, decl: &Declaration) -> String {
-        match &decl.kind {
-            DeclKind::VarDecl(var_decl) => self.print_var_decl(var_decl),
-            DeclKind::Statement(stmt) => self.print_statement(stmt),
-        }
+    /// Leaves out the `(group ...)` nodes parentheses in the source produce,
+    /// printing only what they contain, so programs that differ only in
+    /// redundant parentheses print the same.
+    pub fn omit_groupings(mut self, omit: bool) -> Self {
+        self.omit_groupings = omit;
+        self
+    }
+
+    pub fn print_declaration(&mut self, decl: &Declaration) -> String {
+        self.visit_decl(decl)
     }
 
-    pub fn print_var_decl(&self, var_decl: &VarDecl) -> String {
+    pub fn print_var_decl(&mut self, var_decl: &VarDecl) -> String {
+        self.visit_var_decl(var_decl)
+    }
+
+    pub fn print_multi_var_decl(&mut self, var_decls: &[VarDecl]) -> String {
+        let inner = var_decls
+            .iter()
+            .map(|var_decl| self.print_var_binding(var_decl))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let keyword = var_decls.first().map_or("var", VarDecl::keyword);
+        format!("{} {};", keyword, inner)
+    }
+
+    /// Prints a single declared name and its initializer, without the `var` or
+    /// `const` keyword.
+    fn print_var_binding(&mut self, var_decl: &VarDecl) -> String {
         match &var_decl.initializer {
-            Some(expr) => format!(
-                "var {} = {};",
-                var_decl.identifier,
-                self.print_expression(expr)
-            ),
-            None => format!("var {};", var_decl.identifier),
+            Some(expr) => format!("{} = {}", var_decl.identifier, self.visit_expr(expr)),
+            None => var_decl.identifier.to_string(),
         }
     }
 
-    pub fn print_statement(&self, stmt: &Statement) -> String {
-        match &stmt.kind {
-            StmtKind::ExprStmt { expression } => format!("{};", self.print_expression(expression)),
-            StmtKind::PrintStmt { expression } => {
-                format!("print {};", self.print_expression(expression))
-            }
-            StmtKind::Block { declarations } => self.print_block(declarations),
-            StmtKind::IfStmt {
-                condition,
-                then_stmt,
-                else_stmt,
-            } => self.print_if_stmt(condition, then_stmt, else_stmt),
-            StmtKind::WhileStmt { condition, do_stmt } => self.print_while_stmt(condition, do_stmt),
-            StmtKind::ForStmt {
-                initializer,
-                condition,
-                update,
-                body,
-            } => self.print_for_statement(initializer, condition, update, body),
-        }
+    pub fn print_statement(&mut self, stmt: &Statement) -> String {
+        self.visit_stmt(stmt)
     }
 
-    pub fn print_block(&self, declarations: &[Declaration]) -> String {
+    pub fn print_block(&mut self, declarations: &[Declaration]) -> String {
         let inner = declarations
             .iter()
-            .map(|decl| self.print_declaration(decl))
+            .map(|decl| self.visit_decl(decl))
             .collect::<Vec<_>>()
             .join("\n");
+        self.print_braced(&inner)
+    }
+
+    fn print_block_expression(
+        &mut self,
+        declarations: &[Declaration],
+        tail: &Option<Box<Expression>>,
+    ) -> String {
+        let mut lines = declarations
+            .iter()
+            .map(|decl| self.visit_decl(decl))
+            .collect::<Vec<_>>();
+        if let Some(tail) = tail {
+            lines.push(self.visit_expr(tail));
+        }
+        self.print_braced(&lines.join("\n"))
+    }
+
+    /// Wraps already printed lines in braces, indenting them by two spaces.
+    fn print_braced(&self, inner: &str) -> String {
         format!(
             "{{\n{}\n}}",
             inner
@@ -79,36 +122,75 @@ impl PrettyPrinter {
         )
     }
 
-    fn print_while_stmt(&self, condition: &Expression, do_stmt: &Statement) -> String {
+    fn print_switch(
+        &mut self,
+        discriminant: &Expression,
+        cases: &[SwitchCase],
+        default: &Option<Vec<Declaration>>,
+    ) -> String {
+        let mut arms = vec![];
+        for case in cases {
+            let label = format!("case {}:", self.visit_expr(&case.value));
+            arms.push(self.print_switch_arm(label, &case.body, case.fallthrough));
+        }
+        if let Some(body) = default {
+            arms.push(self.print_switch_arm("default:".to_string(), body, false));
+        }
+        format!(
+            "switch ({}) {}",
+            self.visit_expr(discriminant),
+            self.print_braced(&arms.join("\n"))
+        )
+    }
+
+    /// Prints a `case` or `default` label followed by its indented body.
+    fn print_switch_arm(
+        &mut self,
+        label: String,
+        body: &[Declaration],
+        fallthrough: bool,
+    ) -> String {
+        let mut lines = vec![label];
+        for decl in body {
+            let printed = self.visit_decl(decl);
+            lines.extend(printed.lines().map(|line| format!("  {}", line)));
+        }
+        if fallthrough {
+            lines.push("  fallthrough;".to_string());
+        }
+        lines.join("\n")
+    }
+
+    fn print_while_stmt(&mut self, condition: &Expression, do_stmt: &Statement) -> String {
         format!(
             "while({}) {}",
-            self.print_expression(condition),
-            self.print_statement(do_stmt)
+            self.visit_expr(condition),
+            self.visit_stmt(do_stmt)
         )
     }
     fn print_for_statement(
-        &self,
+        &mut self,
         initializer: &Option<Box<Declaration>>,
         condition: &Option<Box<Expression>>,
         update: &Option<Box<Expression>>,
         body: &Statement,
     ) -> String {
         let init_str = match initializer {
-            Some(decl) => self.print_declaration(decl),
+            Some(decl) => self.visit_decl(decl),
             None => String::new(),
         };
 
         let cond_str = match condition {
-            Some(expr) => self.print_expression(expr),
+            Some(expr) => self.visit_expr(expr),
             None => String::new(),
         };
 
         let update_str = match update {
-            Some(expr) => self.print_expression(expr),
+            Some(expr) => self.visit_expr(expr),
             None => String::new(),
         };
 
-        let body_str = self.print_statement(body);
+        let body_str = self.visit_stmt(body);
 
         format!(
             "for ({init}; {cond}; {update}) {body}",
@@ -118,80 +200,303 @@ impl PrettyPrinter {
             body = body_str
         )
     }
+    /// Prints an `if` statement, flattening `else if` chains.
+    ///
+    /// An `else` branch that is itself an `if` continues the chain as
+    /// `else if (...)` on the same line, the way it was written, instead of
+    /// being printed one level deeper.
     fn print_if_stmt(
-        &self,
+        &mut self,
         condition: &Expression,
         then_stmt: &Statement,
         else_stmt: &Option<Box<Statement>>,
     ) -> String {
-        let else_part = match else_stmt {
-            Some(stmt) => format!(" else {}", self.print_statement(stmt)),
-            None => String::new(),
-        };
-        format!(
-            "if ({}) {}{}",
-            self.print_expression(condition),
-            self.print_statement(then_stmt),
-            else_part
-        )
+        let mut output = format!(
+            "if ({}) {}",
+            self.visit_expr(condition),
+            self.visit_stmt(then_stmt)
+        );
+        let mut else_branch = else_stmt.as_deref();
+        while let Some(stmt) = else_branch {
+            match &stmt.kind {
+                StmtKind::IfStmt {
+                    condition,
+                    then_stmt,
+                    else_stmt,
+                } => {
+                    output.push_str(&format!(
+                        " else if ({}) {}",
+                        self.visit_expr(condition),
+                        self.visit_stmt(then_stmt)
+                    ));
+                    else_branch = else_stmt.as_deref();
+                }
+                _ => {
+                    output.push_str(&format!(" else {}", self.visit_stmt(stmt)));
+                    else_branch = None;
+                }
+            }
+        }
+        output
+    }
+
+    pub fn print_expression(&mut self, expr: &Expression) -> String {
+        self.visit_expr(expr)
     }
 
-    pub fn print_expression(&self, expr: &Expression) -> String {
+    /// Prints an expression, placing its `position` annotation, which is
+    /// empty unless positions are enabled.
+    fn print_expression_kind(&mut self, expr: &Expression, position: &str) -> String {
         match &expr.kind {
-            ExprKind::Lit { value } => self.print_literal(value),
-            ExprKind::Var { identifier } => identifier.clone(),
-            ExprKind::Grouping { expression } => self.print_grouping(expression),
-            ExprKind::Unary { operator, right } => self.print_unary(operator, right),
+            ExprKind::Lit { value } => format!("{}{}", self.print_literal(value), position),
+            ExprKind::Var { identifier } => format!("{}{}", identifier, position),
+            ExprKind::Grouping { expression } => self.print_grouping(expression, position),
+            ExprKind::BlockExpr { declarations, tail } => {
+                in_front(position, self.print_block_expression(declarations, tail))
+            }
+            ExprKind::MapLit { entries } => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = self.visit_expr(key);
+                        format!("{}: {}", key, self.visit_expr(value))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                in_front(position, format!("{{{}}}", entries))
+            }
+            ExprKind::Index { object, index } => self.print_index(object, index, position),
+            ExprKind::IndexAssignment {
+                object,
+                index,
+                value,
+            } => {
+                let target = self.print_index(object, index, "");
+                in_front(position, format!("{} = {}", target, self.visit_expr(value)))
+            }
+            ExprKind::Unary { operator, right } => format!(
+                "({} {})",
+                after(&operator.to_string(), position),
+                self.visit_expr(right)
+            ),
+            ExprKind::TypeOf { operand } => format!(
+                "({} {})",
+                after("typeof", position),
+                self.visit_expr(operand)
+            ),
+            ExprKind::IncDec {
+                identifier,
+                operator,
+                prefix,
+            } => {
+                if *prefix {
+                    format!(
+                        "({} {})",
+                        after(&operator.to_string(), position),
+                        identifier
+                    )
+                } else {
+                    in_front(position, format!("({} {})", identifier, operator))
+                }
+            }
             ExprKind::Binary {
                 left,
                 operator,
                 right,
-            } => self.print_binary(left, operator, right),
+                ..
+            } => self.print_binary(left, operator, right, position),
             ExprKind::Logical {
                 left,
                 logic_op,
                 right,
-            } => self.print_logical(left, logic_op, right),
-            ExprKind::Assignment { identifier, value } => self.print_assignment(identifier, value),
+                ..
+            } => in_front(position, self.print_logical(left, logic_op, right)),
+            ExprKind::Assignment { identifier, value } => {
+                in_front(position, self.print_assignment(*identifier, value))
+            }
+            ExprKind::GlobalAssignment { identifier, value } => in_front(
+                position,
+                format!("global {}", self.print_assignment(*identifier, value)),
+            ),
+            ExprKind::Sequence { expressions } => self.print_sequence(expressions, position),
         }
     }
 
     fn print_literal(&self, value: &Literal) -> String {
         match value {
-            Literal::Number(n) => n.to_string(),
+            Literal::Number(n) => format_number(*n),
             Literal::String(s) => format!("\"{}\"", s),
             Literal::Boolean(b) => b.to_string(),
             Literal::Nil => "nil".to_string(),
+            Literal::Map(_) => value.to_string(),
         }
     }
 
-    fn print_grouping(&self, expression: &Expression) -> String {
-        format!("(group {})", self.print_expression(expression))
+    fn print_index(&mut self, object: &Expression, index: &Expression, position: &str) -> String {
+        format!(
+            "({} {} {})",
+            after("index", position),
+            self.visit_expr(object),
+            self.visit_expr(index)
+        )
     }
 
-    fn print_unary(&self, operator: &Operator, right: &Expression) -> String {
-        format!("({} {})", operator, self.print_expression(right))
+    /// Prints a grouping, or only what it contains when groupings are
+    /// omitted, in which case its position is left out along with it.
+    fn print_grouping(&mut self, expression: &Expression, position: &str) -> String {
+        if self.omit_groupings {
+            return self.visit_expr(expression);
+        }
+        format!(
+            "({} {})",
+            after("group", position),
+            self.visit_expr(expression)
+        )
     }
 
-    fn print_binary(&self, left: &Expression, operator: &Operator, right: &Expression) -> String {
+    fn print_binary(
+        &mut self,
+        left: &Expression,
+        operator: &Operator,
+        right: &Expression,
+        position: &str,
+    ) -> String {
         format!(
             "({} {} {})",
-            operator,
-            self.print_expression(left),
-            self.print_expression(right)
+            after(&operator.to_string(), position),
+            self.visit_expr(left),
+            self.visit_expr(right)
         )
     }
 
-    fn print_logical(&self, left: &Expression, logic_op: &TokenType, right: &Expression) -> String {
+    fn print_logical(
+        &mut self,
+        left: &Expression,
+        logic_op: &TokenType,
+        right: &Expression,
+    ) -> String {
         format!(
             "({} {} {})",
-            self.print_expression(left),
+            self.visit_expr(left),
             logic_op,
-            self.print_expression(right)
+            self.visit_expr(right)
         )
     }
 
-    fn print_assignment(&self, identifier: &str, value: &Expression) -> String {
-        format!("{} = {}", identifier, self.print_expression(value))
+    fn print_sequence(&mut self, expressions: &[Expression], position: &str) -> String {
+        let inner = expressions
+            .iter()
+            .map(|expr| self.visit_expr(expr))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("({} {})", after(",", position), inner)
+    }
+
+    fn print_assignment(&mut self, identifier: Symbol, value: &Expression) -> String {
+        format!("{} = {}", identifier, self.visit_expr(value))
+    }
+}
+
+impl Visitor<String> for PrettyPrinter {
+    fn visit_decl(&mut self, decl: &Declaration) -> String {
+        match &decl.kind {
+            DeclKind::VarDecl(var_decl) => self.visit_var_decl(var_decl),
+            DeclKind::MultiVarDecl(var_decls) => self.print_multi_var_decl(var_decls),
+            DeclKind::Statement(stmt) => self.visit_stmt(stmt),
+        }
     }
+
+    fn visit_var_decl(&mut self, var_decl: &VarDecl) -> String {
+        format!(
+            "{} {};",
+            var_decl.keyword(),
+            self.print_var_binding(var_decl)
+        )
+    }
+
+    fn visit_stmt(&mut self, stmt: &Statement) -> String {
+        match &stmt.kind {
+            StmtKind::ExprStmt { expression } => format!("{};", self.visit_expr(expression)),
+            StmtKind::PrintStmt { expressions } => {
+                let arguments = expressions
+                    .iter()
+                    .map(|expr| self.visit_expr(expr))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("print {};", arguments)
+            }
+            StmtKind::Block { declarations } => self.print_block(declarations),
+            StmtKind::Break { label } => format!("break{};", print_jump_target(label)),
+            StmtKind::Continue { label } => format!("continue{};", print_jump_target(label)),
+            StmtKind::Switch {
+                discriminant,
+                cases,
+                default,
+            } => self.print_switch(discriminant, cases, default),
+            StmtKind::IfStmt {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => self.print_if_stmt(condition, then_stmt, else_stmt),
+            StmtKind::WhileStmt {
+                condition,
+                do_stmt,
+                label,
+            } => format!(
+                "{}{}",
+                print_label(label),
+                self.print_while_stmt(condition, do_stmt)
+            ),
+            StmtKind::ForStmt {
+                initializer,
+                condition,
+                update,
+                body,
+                label,
+            } => format!(
+                "{}{}",
+                print_label(label),
+                self.print_for_statement(initializer, condition, update, body)
+            ),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expression) -> String {
+        let position = if self.positions {
+            format!("@{}:{}", expr.line, expr.column)
+        } else {
+            String::new()
+        };
+        self.print_expression_kind(expr, &position)
+    }
+}
+
+/// Prints an operator or other head word followed by the `position`
+/// annotation, if there is one.
+fn after(head: &str, position: &str) -> String {
+    if position.is_empty() {
+        head.to_string()
+    } else {
+        format!("{} {}", head, position)
+    }
+}
+
+/// Puts the `position` annotation, if there is one, in front of a node that
+/// starts with neither an operator nor a single word.
+fn in_front(position: &str, printed: String) -> String {
+    if position.is_empty() {
+        printed
+    } else {
+        format!("{} {}", position, printed)
+    }
+}
+
+/// Prints the `label: ` prefix of a labeled loop, or nothing.
+fn print_label(label: &Option<Symbol>) -> String {
+    label.map(|l| format!("{}: ", l)).unwrap_or_default()
+}
+
+/// Prints the ` label` following `break` or `continue`, or nothing.
+fn print_jump_target(label: &Option<Symbol>) -> String {
+    label.map(|l| format!(" {}", l)).unwrap_or_default()
 }
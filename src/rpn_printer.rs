@@ -0,0 +1,118 @@
+//! Provides a reverse Polish notation printer for Lox expressions.
+//!
+//! This module contains the `RpnPrinter` struct, a debugging aid that renders
+//! expressions in postfix form, so `(1 + 2) * 3` becomes `1 2 + 3 *`.
+use crate::ast::{ExprKind, Expression};
+use crate::token::{Operator, TokenType};
+
+#[derive(Default)]
+pub struct RpnPrinter;
+
+impl RpnPrinter {
+    pub fn new() -> Self {
+        RpnPrinter
+    }
+
+    /// Renders an expression in postfix form.
+    ///
+    /// Grouping nodes are transparent and unary operators are prefixed with `u`
+    /// to tell them apart from their binary counterparts (e.g. `u-`).
+    pub fn print_expression(&self, expr: &Expression) -> String {
+        match &expr.kind {
+            ExprKind::Lit { value } => value.to_string(),
+            ExprKind::Var { identifier } => identifier.to_string(),
+            ExprKind::Grouping { expression } => self.print_expression(expression),
+            // Statements have no postfix form, so only the block's value is shown.
+            ExprKind::BlockExpr { tail, .. } => match tail {
+                Some(tail) => format!("{{ ... {} }}", self.print_expression(tail)),
+                None => "{ ... }".to_string(),
+            },
+            // The entry count follows the keys and values, e.g. `"a" 1 {1}`.
+            ExprKind::MapLit { entries } => {
+                let operands = entries.iter().flat_map(|(key, value)| {
+                    [self.print_expression(key), self.print_expression(value)]
+                });
+                operands
+                    .chain(std::iter::once(format!("{{{}}}", entries.len())))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            ExprKind::Index { object, index } => format!(
+                "{} {} []",
+                self.print_expression(object),
+                self.print_expression(index)
+            ),
+            ExprKind::IndexAssignment {
+                object,
+                index,
+                value,
+            } => format!(
+                "{} {} {} []=",
+                self.print_expression(object),
+                self.print_expression(index),
+                self.print_expression(value)
+            ),
+            ExprKind::Unary { operator, right } => self.print_unary(operator, right),
+            ExprKind::TypeOf { operand } => format!("{} typeof", self.print_expression(operand)),
+            ExprKind::IncDec {
+                identifier,
+                operator,
+                prefix,
+            } => {
+                let fixity = if *prefix { "pre" } else { "post" };
+                format!("{} {}{}", identifier, fixity, operator)
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => self.print_binary(left, operator, right),
+            ExprKind::Logical {
+                left,
+                logic_op,
+                right,
+                ..
+            } => self.print_logical(left, logic_op, right),
+            ExprKind::Assignment { identifier, value } => {
+                format!("{} {} =", identifier, self.print_expression(value))
+            }
+            ExprKind::GlobalAssignment { identifier, value } => {
+                format!("{} {} global=", identifier, self.print_expression(value))
+            }
+            ExprKind::Sequence { expressions } => self.print_sequence(expressions),
+        }
+    }
+
+    /// Renders a sequence as a left-associative chain of `,` operators.
+    fn print_sequence(&self, expressions: &[Expression]) -> String {
+        let mut parts = expressions.iter().map(|expr| self.print_expression(expr));
+        let mut output = parts.next().unwrap_or_default();
+        for part in parts {
+            output = format!("{} {} ,", output, part);
+        }
+        output
+    }
+
+    fn print_unary(&self, operator: &Operator, right: &Expression) -> String {
+        format!("{} u{}", self.print_expression(right), operator)
+    }
+
+    fn print_binary(&self, left: &Expression, operator: &Operator, right: &Expression) -> String {
+        format!(
+            "{} {} {}",
+            self.print_expression(left),
+            self.print_expression(right),
+            operator
+        )
+    }
+
+    fn print_logical(&self, left: &Expression, logic_op: &TokenType, right: &Expression) -> String {
+        format!(
+            "{} {} {}",
+            self.print_expression(left),
+            self.print_expression(right),
+            logic_op
+        )
+    }
+}
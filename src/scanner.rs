@@ -1,220 +1,365 @@
 //! Implements the lexical analyzer (scanner) for the Lox language.
 //!
 //! This module is responsible for breaking down the input source code into a series of tokens.
-use std::{iter::Peekable, str::Chars};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    rc::Rc,
+    str::Chars,
+};
 
 use crate::{
-    error_reporter::ErrorReporter,
+    error_reporter::{Diagnostic, ErrorReporter},
+    interner::Symbol,
     token::{Literal, Operator, Token, TokenType, KEYWORDS},
 };
 
+/// Scans `source` without printing anything, returning the tokens together
+/// with any lexical errors.
+pub fn scan(source: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut scanner = Scanner::new(source);
+    scanner.error_reporter = ErrorReporter::collecting();
+    let tokens = scanner.scan_tokens();
+    (tokens, scanner.error_reporter.take_diagnostics())
+}
+
+/// Converts the lexeme of a number literal found at `line` and `column` to
+/// its value.
+///
+/// A lexeme that does not parse, such as `1.2.3`, is reported spanning the
+/// whole lexeme and yields `0`, so that scanning can go on. The scanner only
+/// collects digits with at most one point, but a bad lexeme must not bring
+/// it down.
+pub fn parse_number(
+    lexeme: &str,
+    line: usize,
+    column: usize,
+    error_reporter: &mut ErrorReporter,
+) -> f64 {
+    lexeme.parse().unwrap_or_else(|_| {
+        error_reporter.error_spanning(
+            line,
+            column,
+            lexeme.chars().count(),
+            &format!("Invalid number '{}'.", lexeme),
+        );
+        0.0
+    })
+}
+
+/// A `//` comment standing alone on its line, kept as trivia for tools such
+/// as the formatter since it produces no token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub line: usize,
+    /// The text after the `//`, as written.
+    pub text: String,
+}
+
 /// The scanner for Lox source code.
 ///
 /// Uses a peekable iterator and keeps
 /// track of the current line and column.
+///
+/// The scanner is itself an iterator yielding one token at a time, so a large
+/// source never has to be held as tokens all at once. Lexical errors are
+/// reported through its `error_reporter` as they are found, and the iterator
+/// ends with the input.
+///
+/// Lines and columns are 1-based. Columns count Unicode scalar values (`char`s),
+/// not bytes, so in `"café" + x` the `+` is at column 8 even though it is the
+/// ninth byte of the line.
 pub struct Scanner<'a> {
     chars: Peekable<Chars<'a>>,
+    /// Line of the last consumed character.
     line: usize,
+    /// Column of the last consumed character, 0 before the first one of a line.
     column: usize,
+    /// Position of the first character of the token being scanned.
+    start_line: usize,
+    start_column: usize,
+    /// Maps keyword text to its token type; anything else is an identifier.
+    keywords: Cow<'a, HashMap<&'static str, TokenType>>,
+    /// Line the last token ended on, to tell comments alone on their line
+    /// from those following code.
+    last_token_line: usize,
+    /// The text of every string literal scanned so far, so repeated literals
+    /// share one allocation.
+    strings: HashSet<Rc<str>>,
+    /// Line comments alone on their line, in source order.
+    pub comments: Vec<Comment>,
     pub error_reporter: ErrorReporter,
 }
 
 impl<'a> Scanner<'a> {
-    /// Creates a new Scanner instance.    
+    /// Creates a new Scanner instance.
+    ///
+    /// A byte order mark and a `#!` shebang line at the very start of the
+    /// source are skipped. The shebang's newline is kept, so the program still
+    /// starts on line 2.
     pub fn new(source: &'a str) -> Self {
+        let source = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+        let source = match source.strip_prefix("#!") {
+            Some(rest) => &rest[rest.find('\n').unwrap_or(rest.len())..],
+            None => source,
+        };
         Scanner {
             chars: source.chars().peekable(),
             line: 1,
             column: 0,
+            start_line: 1,
+            start_column: 0,
+            keywords: Cow::Borrowed(&KEYWORDS),
+            last_token_line: 0,
+            strings: HashSet::new(),
+            comments: vec![],
             error_reporter: ErrorReporter::new(),
         }
     }
 
-    /// Scans the input and produces a vector of tokens.
+    /// Creates a Scanner that recognizes `keywords` instead of the standard
+    /// keyword table, e.g. to alias `function` to `TokenType::Fun`.
+    pub fn with_keywords(source: &'a str, keywords: HashMap<&'static str, TokenType>) -> Self {
+        Scanner {
+            keywords: Cow::Owned(keywords),
+            ..Scanner::new(source)
+        }
+    }
+
+    /// Scans the whole input and produces a vector of tokens.
     ///
-    /// This is the main method that processes the entire input and generates all tokens.
+    /// This collects the tokens the scanner yields as an iterator.
     pub fn scan_tokens(&mut self) -> Vec<Token> {
-        let mut tokens: Vec<Token> = vec![];
-        while let Some(c) = self.advance() {
-            match c {
-                //Single Character Tokens
-                '(' => tokens.push(self.add_single_character_token(TokenType::LeftParen, c)),
-                ')' => tokens.push(self.add_single_character_token(TokenType::RightParen, c)),
-                '{' => tokens.push(self.add_single_character_token(TokenType::LeftBrace, c)),
-                '}' => tokens.push(self.add_single_character_token(TokenType::RightBrace, c)),
-                ',' => tokens.push(self.add_single_character_token(TokenType::Comma, c)),
-                '.' => tokens.push(self.add_single_character_token(TokenType::Dot, c)),
-                '-' => tokens
-                    .push(self.add_single_character_token(TokenType::Operator(Operator::Minus), c)),
-                '+' => tokens
-                    .push(self.add_single_character_token(TokenType::Operator(Operator::Plus), c)),
-                ';' => tokens.push(self.add_single_character_token(TokenType::Semicolon, c)),
+        self.by_ref().collect()
+    }
 
-                '*' => {
-                    if self.match_next('/') {
-                        self.error_reporter.error(self.line, self.column, "Unexpected closing comment marker '*/' without a corresponding opening '/*'.");
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(TokenType::Operator(Operator::Star), c),
-                        )
-                    }
+    /// Scans the token starting with the already consumed character `c`.
+    ///
+    /// Returns `None` for characters that produce no token, such as
+    /// whitespace, comments and invalid characters, which are reported.
+    fn scan_token(&mut self, c: char) -> Option<Token> {
+        match c {
+            //Single Character Tokens
+            '(' => Some(self.add_single_character_token(TokenType::LeftParen, c)),
+            ')' => Some(self.add_single_character_token(TokenType::RightParen, c)),
+            '{' => Some(self.add_single_character_token(TokenType::LeftBrace, c)),
+            '}' => Some(self.add_single_character_token(TokenType::RightBrace, c)),
+            '[' => Some(self.add_single_character_token(TokenType::LeftBracket, c)),
+            ']' => Some(self.add_single_character_token(TokenType::RightBracket, c)),
+            ',' => Some(self.add_single_character_token(TokenType::Comma, c)),
+            '.' => Some(self.add_single_character_token(TokenType::Dot, c)),
+            '-' => {
+                if self.match_next('-') {
+                    Some(self.add_token(TokenType::Operator(Operator::MinusMinus), "--", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Minus), c))
                 }
-                //Operators
-                '!' => {
-                    if self.match_next('=') {
-                        tokens.push(self.add_token(
-                            TokenType::Operator(Operator::BangEqual),
-                            "!=".to_string(),
-                            None,
-                        ))
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(TokenType::Operator(Operator::Bang), c),
-                        )
-                    }
+            }
+            '+' => {
+                if self.match_next('+') {
+                    Some(self.add_token(TokenType::Operator(Operator::PlusPlus), "++", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Plus), c))
                 }
-                '=' => {
-                    if self.match_next('=') {
-                        tokens.push(self.add_token(
-                            TokenType::Operator(Operator::EqualEqual),
-                            "==".to_string(),
-                            None,
-                        ))
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(
-                                TokenType::Operator(Operator::Equal),
-                                c,
-                            ),
-                        )
-                    }
+            }
+            ';' => Some(self.add_single_character_token(TokenType::Semicolon, c)),
+            ':' => Some(self.add_single_character_token(TokenType::Colon, c)),
+            '&' => Some(self.add_single_character_token(TokenType::Operator(Operator::Amp), c)),
+            '|' => Some(self.add_single_character_token(TokenType::Operator(Operator::Pipe), c)),
+            '^' => Some(self.add_single_character_token(TokenType::Operator(Operator::Caret), c)),
+
+            '*' => {
+                if self.match_next('/') {
+                    self.error_reporter.error(self.line, self.column, "Unexpected closing comment marker '*/' without a corresponding opening '/*'.");
+                    None
+                } else if self.match_next('*') {
+                    Some(self.add_token(TokenType::Operator(Operator::StarStar), "**", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Star), c))
                 }
-                '>' => {
-                    if self.match_next('=') {
-                        tokens.push(self.add_token(
-                            TokenType::Operator(Operator::GreaterEqual),
-                            ">=".to_string(),
-                            None,
-                        ))
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(
-                                TokenType::Operator(Operator::Greater),
-                                c,
-                            ),
-                        )
-                    }
+            }
+            //Operators
+            '!' => {
+                if self.match_next('=') {
+                    Some(self.add_token(TokenType::Operator(Operator::BangEqual), "!=", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Bang), c))
                 }
-                '<' => {
-                    if self.match_next('=') {
-                        tokens.push(self.add_token(
-                            TokenType::Operator(Operator::LessEqual),
-                            "<=".to_string(),
-                            None,
-                        ))
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(TokenType::Operator(Operator::Less), c),
-                        )
-                    }
+            }
+            '=' => {
+                if self.match_next('=') {
+                    Some(self.add_token(TokenType::Operator(Operator::EqualEqual), "==", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Equal), c))
                 }
-                '/' => {
-                    if self.match_next('/') {
-                        //Handle comments by ignoring untill newline
-                        while matches!(self.chars.peek(), Some(&c) if c != '\n') {
-                            self.advance();
-                        }
-                    } else if self.match_next('*') {
-                        // Multi-line comment
-                        loop {
-                            match (self.advance(), self.chars.peek()) {
-                                (Some('\n'), _) => {
-                                    self.line += 1;
-                                    self.column = 1;
-                                }
-                                (Some('*'), Some(&'/')) => {
-                                    self.advance();
-                                    break;
-                                }
-                                (None, _) => {
-                                    self.error_reporter.error(
-                                        self.line,
-                                        self.column,
-                                        "Unterminated multi-line comment.",
-                                    );
-                                    break;
-                                }
-                                _ => {}
+            }
+            '>' => {
+                if self.match_next('=') {
+                    Some(self.add_token(TokenType::Operator(Operator::GreaterEqual), ">=", None))
+                } else if self.match_next('>') {
+                    Some(self.add_token(TokenType::Operator(Operator::Shr), ">>", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Greater), c))
+                }
+            }
+            '<' => {
+                if self.match_next('=') {
+                    Some(self.add_token(TokenType::Operator(Operator::LessEqual), "<=", None))
+                } else if self.match_next('<') {
+                    Some(self.add_token(TokenType::Operator(Operator::Shl), "<<", None))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Less), c))
+                }
+            }
+            '/' => {
+                if self.match_next('/') {
+                    //Handle comments by ignoring untill newline
+                    let mut text = String::new();
+                    while let Some(&c) = self.chars.peek().filter(|&&c| c != '\n') {
+                        text.push(c);
+                        self.advance();
+                    }
+                    if self.start_line != self.last_token_line {
+                        self.comments.push(Comment {
+                            line: self.start_line,
+                            text,
+                        });
+                    }
+                    None
+                } else if self.match_next('*') {
+                    // Multi-line comment
+                    loop {
+                        match (self.advance(), self.chars.peek()) {
+                            (Some('\n'), _) => {
+                                self.line += 1;
+                                self.column = 0;
                             }
+                            (Some('*'), Some(&'/')) => {
+                                self.advance();
+                                break;
+                            }
+                            (None, _) => {
+                                self.error_reporter.error(
+                                    self.line,
+                                    self.column,
+                                    "Unterminated multi-line comment.",
+                                );
+                                break;
+                            }
+                            _ => {}
                         }
-                    } else {
-                        tokens.push(
-                            self.add_single_character_token(
-                                TokenType::Operator(Operator::Slash),
-                                c,
-                            ),
-                        )
                     }
+                    None
+                } else {
+                    Some(self.add_single_character_token(TokenType::Operator(Operator::Slash), c))
                 }
+            }
 
-                //Handle String Literals
-                '"' => {
-                    let mut lexeme = String::new();
-                    lexeme.push('"'); // Include the opening quote in the lexeme
-                    let mut closed = false;
-                    while let Some(&c) = self.chars.peek() {
-                        self.advance(); // Consume the character
-                        if c == '"' {
-                            lexeme.push(c); // Include the closing quote in the lexeme
-                            closed = true;
-                            break;
-                        }
-                        if c == '\n' {
-                            self.line += 1;
-                            self.column = 1;
-                        }
-                        lexeme.push(c);
-                    }
-                    if !closed {
-                        self.error_reporter
-                            .error(self.line, self.column, "Unterminated string.");
-                    } else {
-                        let string_content = lexeme.trim_matches('"').to_string();
-                        tokens.push(self.add_token(
-                            TokenType::String,
-                            lexeme,
-                            Some(Literal::String(string_content)),
-                        ));
+            //Handle String Literals
+            '"' => {
+                let mut lexeme = String::new();
+                lexeme.push('"'); // Include the opening quote in the lexeme
+                let mut closed = false;
+                // A string ends at the end of its line at the latest, so a
+                // missing closing quote does not swallow the rest of the file.
+                while let Some(&c) = self.chars.peek().filter(|&&c| c != '\n') {
+                    self.advance(); // Consume the character
+                    if c == '"' {
+                        lexeme.push(c); // Include the closing quote in the lexeme
+                        closed = true;
+                        break;
                     }
+                    lexeme.push(c);
+                }
+                if !closed {
+                    self.error_reporter
+                        .error(self.line, self.column, "Unterminated string.");
+                    None
+                } else {
+                    let string_content = self.intern_string(lexeme.trim_matches('"'));
+                    Some(self.add_token(
+                        TokenType::String,
+                        &lexeme,
+                        Some(Literal::String(string_content)),
+                    ))
                 }
-                // Handle whitespace by ignoring it
-                ' ' | '\r' | '\t' => {}
-                '\n' => {
-                    self.line += 1;
-                    self.column = 1;
+            }
+            '?' => {
+                if self.match_next('?') {
+                    Some(self.add_token(
+                        TokenType::Operator(Operator::QuestionQuestion),
+                        "??",
+                        None,
+                    ))
+                } else {
+                    Some(self.add_single_character_token(TokenType::Question, c))
                 }
+            }
+            // Handle whitespace by ignoring it
+            ' ' | '\r' | '\t' => None,
+            '\n' => {
+                self.line += 1;
+                self.column = 0;
+                None
+            }
 
-                _ => {
-                    if c.is_ascii_digit() {
-                        tokens.push(self.number(c))
-                    } else if c.is_ascii_alphabetic() || c == '_' {
-                        tokens.push(self.identifier(c))
-                    } else {
-                        self.error_reporter
-                            .error(self.line, self.column, "Unexepected character.")
-                    }
+            _ => {
+                if c.is_ascii_digit() {
+                    Some(self.number(c))
+                } else if c.is_ascii_alphabetic() || c == '_' {
+                    Some(self.identifier(c))
+                } else if c.is_whitespace() {
+                    // Other Unicode whitespace, such as a non-breaking
+                    // space pasted from a web page, separates tokens too.
+                    None
+                } else {
+                    self.unexpected_character(c);
+                    None
                 }
             }
         }
-        tokens
+    }
+
+    /// Returns the shared text for a string literal, allocating it only the
+    /// first time `text` is seen.
+    fn intern_string(&mut self, text: &str) -> Rc<str> {
+        if let Some(shared) = self.strings.get(text) {
+            return Rc::clone(shared);
+        }
+        let shared: Rc<str> = Rc::from(text);
+        self.strings.insert(Rc::clone(&shared));
+        shared
+    }
+
+    /// Reports a character that cannot start a token, showing it escaped
+    /// and with its code point, since it may well be invisible.
+    fn unexpected_character(&mut self, c: char) {
+        self.error_reporter.error_spanning(
+            self.start_line,
+            self.start_column,
+            1,
+            &format!(
+                "Unexpected character '{}' (U+{:04X}).",
+                c.escape_debug(),
+                c as u32
+            ),
+        );
     }
 
     fn add_single_character_token(&self, token_type: TokenType, c: char) -> Token {
-        self.add_token(token_type, c.to_string(), None)
+        self.add_token(token_type, c.encode_utf8(&mut [0; 4]), None)
     }
 
-    fn add_token(&self, token_type: TokenType, lexeme: String, literal: Option<Literal>) -> Token {
-        Token::new(token_type, lexeme, literal, self.line, self.column)
+    /// Creates a token positioned at the start of its lexeme.
+    fn add_token(&self, token_type: TokenType, lexeme: &str, literal: Option<Literal>) -> Token {
+        Token::new(
+            token_type,
+            lexeme,
+            literal,
+            self.start_line,
+            self.start_column,
+        )
     }
 
     fn match_next(&mut self, next_char: char) -> bool {
@@ -223,6 +368,7 @@ impl<'a> Scanner<'a> {
             true
         }
     }
+    /// Scans a number literal starting with `first_digit`, see `parse_number`.
     fn number(&mut self, first_digit: char) -> Token {
         let mut has_decimal = false;
         let mut lexeme = first_digit.to_string();
@@ -248,11 +394,13 @@ impl<'a> Scanner<'a> {
                 _ => break,
             }
         }
-        self.add_token(
-            TokenType::Number,
-            lexeme.clone(),
-            Some(Literal::Number(lexeme.parse().unwrap())),
-        )
+        let value = parse_number(
+            &lexeme,
+            self.start_line,
+            self.start_column,
+            &mut self.error_reporter,
+        );
+        self.add_token(TokenType::Number, &lexeme, Some(Literal::Number(value)))
     }
 
     fn identifier(&mut self, c: char) -> Token {
@@ -266,16 +414,26 @@ impl<'a> Scanner<'a> {
                 _ => break,
             }
         }
-        let token_type = KEYWORDS
+        let token_type = self
+            .keywords
             .get(lexeme.as_str())
             .cloned()
             .unwrap_or(TokenType::Identifier);
-        match token_type {
-            TokenType::Nil => self.add_token(token_type, lexeme, Some(Literal::Nil)),
-            TokenType::True => self.add_token(token_type, lexeme, Some(Literal::Boolean(true))),
-            TokenType::False => self.add_token(token_type, lexeme, Some(Literal::Boolean(false))),
-            _ => self.add_token(token_type, lexeme, None),
-        }
+        let literal = match token_type {
+            TokenType::Nil => Some(Literal::Nil),
+            TokenType::True => Some(Literal::Boolean(true)),
+            TokenType::False => Some(Literal::Boolean(false)),
+            TokenType::Infinity => Some(Literal::Number(f64::INFINITY)),
+            TokenType::NaN => Some(Literal::Number(f64::NAN)),
+            _ => None,
+        };
+        Token::from_symbol(
+            token_type,
+            Symbol::intern(&lexeme),
+            literal,
+            self.start_line,
+            self.start_column,
+        )
     }
 
     fn advance(&mut self) -> Option<char> {
@@ -284,3 +442,19 @@ impl<'a> Scanner<'a> {
         c
     }
 }
+
+impl Iterator for Scanner<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        while let Some(c) = self.advance() {
+            self.start_line = self.line;
+            self.start_column = self.column;
+            if let Some(token) = self.scan_token(c) {
+                self.last_token_line = self.line;
+                return Some(token);
+            }
+        }
+        None
+    }
+}
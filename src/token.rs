@@ -3,10 +3,16 @@
 //! It includes the `Token` struct, `Literal` and `Operator` enums, and the `TokenType` enum
 //! which are fundamental to lexical analysis and parsing in the Lox language implementation.
 
+use crate::{error_reporter::escape_json, interner::Symbol};
 use once_cell::sync::Lazy;
 use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::Ordering,
     collections::HashMap,
     fmt::{Display, Formatter, Result},
+    rc::Rc,
+    str::FromStr,
 };
 
 /// Represents a token in the Lox language.
@@ -18,32 +24,114 @@ use std::{
 pub struct Token {
     pub token_type: TokenType,
     /// The lexeme (actual text) of the token.
-    pub lexeme: String,
+    pub lexeme: Lexeme,
     /// The literal value, if any.
     pub literal: Option<Literal>,
     /// The line number where the token appears.
     pub line: usize,
     /// The column number where the token starts.
     pub column: usize,
+    /// The number of characters of the lexeme.
+    pub length: usize,
+}
+
+/// The source text of a token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lexeme {
+    /// An identifier or keyword, interned so the token holds no text of its
+    /// own and the parser can hand the symbol on without copying.
+    Symbol(Symbol),
+    /// Any other token, such as an operator or a literal, keeping its text.
+    Text(Box<str>),
+}
+
+impl Display for Lexeme {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Lexeme::Symbol(symbol) => write!(f, "{}", symbol),
+            Lexeme::Text(text) => write!(f, "{}", text),
+        }
+    }
 }
 
 impl Token {
     /// Creates a new Token with given properties.
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: &str,
+        literal: Option<Literal>,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Token {
+            token_type,
+            lexeme: Lexeme::Text(lexeme.into()),
+            literal,
+            line,
+            column,
+            length: lexeme.chars().count(),
+        }
+    }
+
+    /// Creates an identifier or keyword token from its interned text.
+    pub fn from_symbol(
+        token_type: TokenType,
+        symbol: Symbol,
         literal: Option<Literal>,
         line: usize,
         column: usize,
     ) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: Lexeme::Symbol(symbol),
             literal,
             line,
             column,
+            length: symbol.with_str(|text| text.chars().count()),
         }
     }
+
+    /// Returns the source text of the token.
+    ///
+    /// The text of identifiers and keywords is copied out of the interner;
+    /// code handling names should use `symbol` instead.
+    pub fn lexeme(&self) -> Cow<'_, str> {
+        match &self.lexeme {
+            Lexeme::Symbol(symbol) => Cow::Owned(symbol.to_string()),
+            Lexeme::Text(text) => Cow::Borrowed(text),
+        }
+    }
+
+    /// Returns the symbol of an identifier or keyword token, interning the
+    /// text of any other token.
+    pub fn symbol(&self) -> Symbol {
+        match &self.lexeme {
+            Lexeme::Symbol(symbol) => *symbol,
+            Lexeme::Text(text) => Symbol::intern(text),
+        }
+    }
+
+    /// Returns the type of the token.
+    pub fn token_type(&self) -> &TokenType {
+        &self.token_type
+    }
+
+    /// Renders the token as a single-line JSON object, e.g.
+    /// `{"type":"Number","lexeme":"1.5","literal":1.5,"line":1,"column":7}`.
+    ///
+    /// `literal` is `null` for tokens without a literal value.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"type\":\"{}\",\"lexeme\":\"{}\",\"literal\":{},\"line\":{},\"column\":{}}}",
+            escape_json(&format!("{:?}", self.token_type)),
+            escape_json(&self.lexeme()),
+            self.literal
+                .as_ref()
+                .map_or("null".to_string(), Literal::to_json),
+            self.line,
+            self.column
+        )
+    }
 }
 
 impl Display for Token {
@@ -60,22 +148,212 @@ impl Display for Token {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     Number(f64),
-    String(String),
+    /// A string, whose text is shared by every copy. Identical string
+    /// literals of one scan share the same text, see `Scanner`.
+    String(Rc<str>),
     Boolean(bool),
     Nil,
+    /// A map from keys to values. Only created at runtime, and shared by
+    /// every copy, so writes through one copy are seen by all of them.
+    Map(Rc<RefCell<HashMap<MapKey, Literal>>>),
+}
+
+impl Literal {
+    /// Returns the name of the literal's type, as reported by `typeof`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Literal::Number(_) => "number",
+            Literal::String(_) => "string",
+            Literal::Boolean(_) => "boolean",
+            Literal::Nil => "nil",
+            Literal::Map(_) => "map",
+        }
+    }
+
+    /// Formats the literal the way `print` shows it.
+    ///
+    /// Unlike `Display`, which keeps strings quoted for source reconstruction,
+    /// strings are written without their surrounding quotes.
+    pub fn display_for_print(&self) -> String {
+        match self {
+            Literal::String(s) => s.to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Formats the literal the way the REPL echoes it.
+    ///
+    /// Strings keep their quotes, so `"1"` and `1` can be told apart, while
+    /// program output through `print` stays unquoted.
+    pub fn repl_display(&self) -> String {
+        self.to_string()
+    }
+
+    /// Renders the literal as a JSON value.
+    ///
+    /// JSON has no infinite or NaN numbers, so those are written as strings
+    /// holding their Lox names.
+    pub fn to_json(&self) -> String {
+        match self {
+            Literal::Number(n) if !n.is_finite() => format!("\"{}\"", format_number(*n)),
+            Literal::Number(n) => format_number(*n),
+            Literal::String(s) => format!("\"{}\"", escape_json(s)),
+            Literal::Boolean(b) => b.to_string(),
+            Literal::Nil => "null".to_string(),
+            Literal::Map(map) => {
+                let entries = sorted_entries(map)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            MapKey::String(s) => s.to_string(),
+                            other => other.to_string(),
+                        };
+                        format!("\"{}\":{}", escape_json(&key), value.to_json())
+                    })
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(","))
+            }
+        }
+    }
 }
 
 impl Display for Literal {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Number(n) => write!(f, "{}", format_number(*n)),
             Literal::String(s) => write!(f, "\"{}\"", s),
             Literal::Boolean(b) => write!(f, "{}", b),
             Literal::Nil => write!(f, "nil"),
+            Literal::Map(map) => {
+                let entries = sorted_entries(map)
+                    .into_iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
         }
     }
 }
 
+/// A value a map can be keyed by: any literal except a map.
+///
+/// `f64` is neither `Eq` nor `Hash`, so numbers are kept by their bit pattern,
+/// normalized so that keys behave like values compared with `==`:
+/// - `-0.0` is stored as `0.0`, so `m[-0]` and `m[0]` are the same entry.
+/// - Every NaN is stored as the same NaN. Unlike `NaN == NaN`, which is false,
+///   a NaN key finds the entry stored under NaN, since a key that never
+///   matched itself could be written but never read back.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Number(u64),
+    String(Rc<str>),
+    Boolean(bool),
+    Nil,
+}
+
+impl MapKey {
+    /// Returns the key for `literal`, or `None` if it is a map, which cannot
+    /// be a key.
+    pub fn from_literal(literal: &Literal) -> Option<MapKey> {
+        match literal {
+            Literal::Number(n) => Some(MapKey::Number(Self::normalize(*n).to_bits())),
+            Literal::String(s) => Some(MapKey::String(s.clone())),
+            Literal::Boolean(b) => Some(MapKey::Boolean(*b)),
+            Literal::Nil => Some(MapKey::Nil),
+            Literal::Map(_) => None,
+        }
+    }
+
+    /// Returns the value the key was made from, up to the normalization of
+    /// zeros and NaNs.
+    pub fn to_literal(&self) -> Literal {
+        match self {
+            MapKey::Number(bits) => Literal::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => Literal::String(s.clone()),
+            MapKey::Boolean(b) => Literal::Boolean(*b),
+            MapKey::Nil => Literal::Nil,
+        }
+    }
+
+    fn normalize(n: f64) -> f64 {
+        if n.is_nan() {
+            f64::NAN
+        } else if n == 0.0 {
+            0.0
+        } else {
+            n
+        }
+    }
+
+    /// Orders keys of different types nil, booleans, numbers, strings.
+    fn rank(&self) -> u8 {
+        match self {
+            MapKey::Nil => 0,
+            MapKey::Boolean(_) => 1,
+            MapKey::Number(_) => 2,
+            MapKey::String(_) => 3,
+        }
+    }
+}
+
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Number(a), MapKey::Number(b)) => {
+                f64::from_bits(*a).total_cmp(&f64::from_bits(*b))
+            }
+            (MapKey::String(a), MapKey::String(b)) => a.cmp(b),
+            (MapKey::Boolean(a), MapKey::Boolean(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for MapKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.to_literal())
+    }
+}
+
+/// Returns copies of a map's entries ordered by key, so maps are always shown
+/// the same way regardless of the order they were filled in.
+fn sorted_entries(map: &RefCell<HashMap<MapKey, Literal>>) -> Vec<(MapKey, Literal)> {
+    let mut entries = map
+        .borrow()
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+}
+
+/// Formats a number the way Lox displays it.
+///
+/// Whole numbers are written without a decimal point (`5.0` as `5`), and
+/// numbers are written out in full below 1e21. From there on they switch to
+/// exponent notation (`1e21`, `1.5e22`) rather than printing long runs of zeros.
+/// The special values are written as the `Infinity`, `-Infinity` and `NaN`
+/// that evaluate to them.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if n.abs() >= 1e21 {
+        format!("{:e}", n)
+    } else if n.fract() == 0.0 {
+        format!("{:.0}", n)
+    } else {
+        n.to_string()
+    }
+}
+
 /// Represents operators in the Lox language.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Operator {
@@ -94,6 +372,19 @@ pub enum Operator {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    PlusPlus,
+    MinusMinus,
+
+    // Bitwise operators.
+    Amp,
+    Pipe,
+    Caret,
+    Shl,
+    Shr,
+
+    /// `??`, yielding its right operand only when the left one is nil.
+    QuestionQuestion,
 }
 
 impl Operator {
@@ -108,6 +399,57 @@ impl Operator {
     }
 }
 
+impl Operator {
+    /// Returns how tightly the operator binds as a binary operator, higher
+    /// binding tighter, matching the levels of the parser.
+    ///
+    /// `-` reports its binary level. `!`, `++` and `--` have no binary form and
+    /// report the unary level.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Equal => precedence::ASSIGNMENT,
+            Operator::QuestionQuestion => precedence::NIL_COALESCING,
+            Operator::EqualEqual | Operator::BangEqual => precedence::EQUALITY,
+            Operator::Pipe => precedence::BITWISE_OR,
+            Operator::Caret => precedence::BITWISE_XOR,
+            Operator::Amp => precedence::BITWISE_AND,
+            Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual => {
+                precedence::COMPARISON
+            }
+            Operator::Shl | Operator::Shr => precedence::SHIFT,
+            Operator::Minus | Operator::Plus => precedence::TERM,
+            Operator::Slash | Operator::Star => precedence::FACTOR,
+            Operator::Bang | Operator::PlusPlus | Operator::MinusMinus => precedence::UNARY,
+            Operator::StarStar => precedence::POWER,
+        }
+    }
+
+    /// Checks if chains of the operator group to the right, as `=` and `**` do.
+    pub fn is_right_associative(&self) -> bool {
+        matches!(self, Operator::Equal | Operator::StarStar)
+    }
+}
+
+/// Binding strength of each level of the grammar, from loosest to tightest.
+pub mod precedence {
+    pub const SEQUENCE: u8 = 1;
+    pub const ASSIGNMENT: u8 = 2;
+    pub const NIL_COALESCING: u8 = 3;
+    pub const LOGICAL: u8 = 4;
+    pub const EQUALITY: u8 = 5;
+    pub const BITWISE_OR: u8 = 6;
+    pub const BITWISE_XOR: u8 = 7;
+    pub const BITWISE_AND: u8 = 8;
+    pub const COMPARISON: u8 = 9;
+    pub const SHIFT: u8 = 10;
+    pub const TERM: u8 = 11;
+    pub const FACTOR: u8 = 12;
+    pub const UNARY: u8 = 13;
+    pub const POWER: u8 = 14;
+    pub const POSTFIX: u8 = 15;
+    pub const PRIMARY: u8 = 16;
+}
+
 impl Display for Operator {
     fn fmt(&self, f: &mut Formatter) -> Result {
         match self {
@@ -123,6 +465,57 @@ impl Display for Operator {
             Operator::GreaterEqual => write!(f, ">="),
             Operator::Less => write!(f, "<"),
             Operator::LessEqual => write!(f, "<="),
+            Operator::StarStar => write!(f, "**"),
+            Operator::PlusPlus => write!(f, "++"),
+            Operator::MinusMinus => write!(f, "--"),
+            Operator::Amp => write!(f, "&"),
+            Operator::Pipe => write!(f, "|"),
+            Operator::Caret => write!(f, "^"),
+            Operator::Shl => write!(f, "<<"),
+            Operator::Shr => write!(f, ">>"),
+            Operator::QuestionQuestion => write!(f, "??"),
+        }
+    }
+}
+
+/// The error returned when parsing text that is not an operator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownOperator(pub String);
+
+impl Display for UnknownOperator {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "Unknown operator '{}'", self.0)
+    }
+}
+
+impl FromStr for Operator {
+    type Err = UnknownOperator;
+
+    /// Parses the source text of an operator, the inverse of its `Display`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "-" => Ok(Operator::Minus),
+            "+" => Ok(Operator::Plus),
+            "/" => Ok(Operator::Slash),
+            "*" => Ok(Operator::Star),
+            "!" => Ok(Operator::Bang),
+            "!=" => Ok(Operator::BangEqual),
+            "=" => Ok(Operator::Equal),
+            "==" => Ok(Operator::EqualEqual),
+            ">" => Ok(Operator::Greater),
+            ">=" => Ok(Operator::GreaterEqual),
+            "<" => Ok(Operator::Less),
+            "<=" => Ok(Operator::LessEqual),
+            "**" => Ok(Operator::StarStar),
+            "++" => Ok(Operator::PlusPlus),
+            "--" => Ok(Operator::MinusMinus),
+            "&" => Ok(Operator::Amp),
+            "|" => Ok(Operator::Pipe),
+            "^" => Ok(Operator::Caret),
+            "<<" => Ok(Operator::Shl),
+            ">>" => Ok(Operator::Shr),
+            "??" => Ok(Operator::QuestionQuestion),
+            _ => Err(UnknownOperator(s.to_string())),
         }
     }
 }
@@ -138,8 +531,12 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Semicolon,
+    Colon,
+    Question,
     Dot,
 
     // Literals.
@@ -150,23 +547,62 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Break,
+    Case,
     Class,
+    Const,
+    Continue,
+    Default,
     Else,
+    Fallthrough,
     False,
     Fun,
     For,
+    Global,
     If,
+    Infinity,
+    NaN,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
     True,
+    TypeOf,
     Var,
     While,
 }
 
+impl TokenType {
+    /// Returns the token type of a keyword, such as `TokenType::While` for
+    /// `"while"`, or `None` if `text` is not a keyword.
+    pub fn keyword(text: &str) -> Option<TokenType> {
+        KEYWORDS.get(text).cloned()
+    }
+
+    /// Returns the precedence of an operator token, `and` and `or` included,
+    /// or `None` for tokens that are not operators.
+    ///
+    /// `and` and `or` share a level, so `a or b and c` groups as `(a or b) and c`.
+    pub fn precedence(&self) -> Option<u8> {
+        match self {
+            TokenType::Operator(op) => Some(op.precedence()),
+            TokenType::And | TokenType::Or => Some(precedence::LOGICAL),
+            _ => None,
+        }
+    }
+
+    /// Checks if the token is a right-associative operator.
+    pub fn is_right_associative(&self) -> bool {
+        match self {
+            TokenType::Operator(op) => op.is_right_associative(),
+            _ => false,
+        }
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -175,26 +611,41 @@ impl Display for TokenType {
             TokenType::RightParen => write!(f, ")"),
             TokenType::LeftBrace => write!(f, "{{"),
             TokenType::RightBrace => write!(f, "}}"),
+            TokenType::LeftBracket => write!(f, "["),
+            TokenType::RightBracket => write!(f, "]"),
             TokenType::Comma => write!(f, ","),
             TokenType::Semicolon => write!(f, ";"),
+            TokenType::Colon => write!(f, ":"),
+            TokenType::Question => write!(f, "?"),
             TokenType::Dot => write!(f, "."),
             TokenType::Identifier => write!(f, "identifier"),
             TokenType::String => write!(f, "string"),
             TokenType::Number => write!(f, "number"),
             TokenType::And => write!(f, "and"),
+            TokenType::Break => write!(f, "break"),
+            TokenType::Case => write!(f, "case"),
             TokenType::Class => write!(f, "class"),
+            TokenType::Const => write!(f, "const"),
+            TokenType::Continue => write!(f, "continue"),
+            TokenType::Default => write!(f, "default"),
             TokenType::Else => write!(f, "else"),
+            TokenType::Fallthrough => write!(f, "fallthrough"),
             TokenType::False => write!(f, "false"),
             TokenType::Fun => write!(f, "fun"),
             TokenType::For => write!(f, "for"),
+            TokenType::Global => write!(f, "global"),
             TokenType::If => write!(f, "if"),
+            TokenType::Infinity => write!(f, "Infinity"),
+            TokenType::NaN => write!(f, "NaN"),
             TokenType::Nil => write!(f, "nil"),
             TokenType::Or => write!(f, "or"),
             TokenType::Print => write!(f, "print"),
             TokenType::Return => write!(f, "return"),
             TokenType::Super => write!(f, "super"),
+            TokenType::Switch => write!(f, "switch"),
             TokenType::This => write!(f, "this"),
             TokenType::True => write!(f, "true"),
+            TokenType::TypeOf => write!(f, "typeof"),
             TokenType::Var => write!(f, "var"),
             TokenType::While => write!(f, "while"),
         }
@@ -207,19 +658,30 @@ impl Display for TokenType {
 pub static KEYWORDS: Lazy<HashMap<&'static str, TokenType>> = Lazy::new(|| {
     let mut map = HashMap::new();
     map.insert("and", TokenType::And);
+    map.insert("break", TokenType::Break);
+    map.insert("case", TokenType::Case);
     map.insert("class", TokenType::Class);
+    map.insert("const", TokenType::Const);
+    map.insert("continue", TokenType::Continue);
+    map.insert("default", TokenType::Default);
     map.insert("else", TokenType::Else);
+    map.insert("fallthrough", TokenType::Fallthrough);
     map.insert("false", TokenType::False);
     map.insert("fun", TokenType::Fun);
     map.insert("for", TokenType::For);
+    map.insert("global", TokenType::Global);
     map.insert("if", TokenType::If);
+    map.insert("Infinity", TokenType::Infinity);
+    map.insert("NaN", TokenType::NaN);
     map.insert("nil", TokenType::Nil);
     map.insert("or", TokenType::Or);
     map.insert("print", TokenType::Print);
     map.insert("return", TokenType::Return);
     map.insert("super", TokenType::Super);
+    map.insert("switch", TokenType::Switch);
     map.insert("this", TokenType::This);
     map.insert("true", TokenType::True);
+    map.insert("typeof", TokenType::TypeOf);
     map.insert("var", TokenType::Var);
     map.insert("while", TokenType::While);
     map
@@ -0,0 +1,118 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn time_reports_each_phase_on_stderr() {
+    let run = run_lox("print 1;", &["--time"]);
+    run.assert_code(0);
+    for phase in ["scanning", "parsing", "interpretation"] {
+        run.assert_error(&format!("[time] {}: ", phase));
+    }
+    run.assert_output("1\n");
+}
+
+#[test]
+fn fold_precomputes_literal_arithmetic() {
+    let run = run_lox(
+        "var x = 1 + 2 * 3;\nprint x - (4 / 2);\nprint \"a\" + \"b\";",
+        &["--fold"],
+    );
+    run.assert_code(0);
+    assert!(
+        run.stdout
+            .starts_with("var x = 7;\nprint (- x 2);\nprint \"ab\";\n"),
+        "{}",
+        run.stdout
+    );
+    run.assert_output("5\nab\n");
+}
+
+#[test]
+fn format_keeps_only_needed_parentheses() {
+    let run = run_lox(
+        "print (1 + 2) * 3;\nprint 1 + (2 * 3);\nprint 1 - (2 - 3);\nprint (1 - 2) - 3;",
+        &["--format"],
+    );
+    run.assert_code(0);
+    assert_eq!(
+        run.stdout,
+        "print (1 + 2) * 3;\nprint 1 + 2 * 3;\nprint 1 - (2 - 3);\nprint 1 - 2 - 3;\n"
+    );
+}
+
+#[test]
+fn check_parses_without_running() {
+    let run = run_lox("print \"side effect\";", &["--check"]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "");
+
+    let run = run_lox("print ;", &["--check"]);
+    run.assert_code(65);
+    run.assert_error("Unexpected token: Semicolon");
+}
+
+#[test]
+fn tokens_json_prints_one_token_per_line() {
+    let run = run_lox("var x = \"a\";", &["--tokens-json"]);
+    run.assert_code(0);
+    assert_eq!(
+        run.stdout,
+        "{\"type\":\"Var\",\"lexeme\":\"var\",\"literal\":null,\"line\":1,\"column\":1}\n\
+         {\"type\":\"Identifier\",\"lexeme\":\"x\",\"literal\":null,\"line\":1,\"column\":5}\n\
+         {\"type\":\"Operator(Equal)\",\"lexeme\":\"=\",\"literal\":null,\"line\":1,\"column\":7}\n\
+         {\"type\":\"String\",\"lexeme\":\"\\\"a\\\"\",\"literal\":\"a\",\"line\":1,\"column\":9}\n\
+         {\"type\":\"Semicolon\",\"lexeme\":\";\",\"literal\":null,\"line\":1,\"column\":12}\n"
+    );
+}
+
+#[test]
+fn format_keeps_comments_above_top_level_declarations() {
+    let run = run_lox(
+        "// The answer.\n// Twice.\nvar x =   42;\nprint x; // trailing\n\n// Separated.\n\nprint x;",
+        &["--format"],
+    );
+    run.assert_code(0);
+    assert_eq!(
+        run.stdout,
+        "// The answer.\n// Twice.\nvar x = 42;\nprint x;\nprint x;\n"
+    );
+}
+
+#[test]
+fn trace_shows_each_evaluation_on_stderr() {
+    let run = run_lox("var x = 1 + 2;\nprint x;", &["--trace"]);
+    run.assert_code(0);
+    assert_eq!(
+        run.stderr,
+        "  eval Literal -> 1\n  eval Literal -> 2\neval Binary + -> 3\nexec Print\neval Variable x -> 3\n"
+    );
+    run.assert_output("3\n");
+}
+
+#[test]
+fn fold_removes_branches_with_literal_conditions() {
+    let run = run_lox(
+        "if (true) print \"yes\"; else print \"no\";\nwhile (false) print \"never\";\nif (1 < 2) print \"kept\";",
+        &["--fold"],
+    );
+    run.assert_code(0);
+    assert!(
+        run.stdout
+            .starts_with("print \"yes\";\n{\n\n}\nprint \"kept\";\n"),
+        "{}",
+        run.stdout
+    );
+    run.assert_output("yes\nkept\n");
+}
+
+#[test]
+fn verify_accepts_a_program_that_formats_back_to_itself() {
+    let run = run_lox(
+        "var x = (1 + 2) * 3;\nvar m = {1: \"a\"};\nif (x > 1) { print -x; } else if (x) print m[1];\nfor (var i = 0; i < 2; i++) continue;",
+        &["--verify"],
+    );
+    run.assert_code(0);
+    assert_eq!(run.stdout, "");
+    assert_eq!(run.stderr, "");
+}
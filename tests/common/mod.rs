@@ -0,0 +1,110 @@
+//! Helpers for running the interpreter binary on Lox source in tests.
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Distinguishes the script files of tests running in parallel.
+static NEXT_SCRIPT: AtomicUsize = AtomicUsize::new(0);
+
+/// What a run of the interpreter printed and how it exited.
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    /// The exit code, or `None` if the process was killed by a signal.
+    pub code: Option<i32>,
+}
+
+impl Run {
+    /// Asserts that the run printed `output` last on stdout.
+    ///
+    /// Running a script prints its pretty-printed program first, so only the
+    /// end of stdout is the program's own output.
+    pub fn assert_output(&self, output: &str) {
+        assert!(
+            self.stdout.ends_with(output),
+            "expected stdout to end with {:?}, got {:?}\nstderr: {}",
+            output,
+            self.stdout,
+            self.stderr
+        );
+    }
+
+    /// Asserts that the run exited with `code`.
+    pub fn assert_code(&self, code: i32) {
+        assert_eq!(
+            self.code,
+            Some(code),
+            "stdout: {}\nstderr: {}",
+            self.stdout,
+            self.stderr
+        );
+    }
+
+    /// Asserts that stderr mentions `message`.
+    pub fn assert_error(&self, message: &str) {
+        assert!(
+            self.stderr.contains(message),
+            "expected stderr to contain {:?}, got {:?}",
+            message,
+            self.stderr
+        );
+    }
+}
+
+/// Writes `source` to a script file and runs the interpreter on it with
+/// `options` placed before the file name.
+pub fn run_lox(source: &str, options: &[&str]) -> Run {
+    run_lox_with_env(source, options, &[])
+}
+
+/// Like `run_lox`, with the environment variables `env` set for the run.
+pub fn run_lox_with_env(source: &str, options: &[&str], env: &[(&str, &str)]) -> Run {
+    let path = env::temp_dir().join(format!(
+        "lox-test-{}-{}.lox",
+        std::process::id(),
+        NEXT_SCRIPT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&path, source).expect("could not write the test script");
+    let output = Command::new(env!("CARGO_BIN_EXE_lox_tree-walk_interpreter"))
+        .args(options)
+        .arg(&path)
+        .envs(env.iter().copied())
+        .output()
+        .expect("could not run the interpreter");
+    let _ = fs::remove_file(&path);
+    Run::from(output)
+}
+
+/// Runs the REPL with `options`, typing `input` into it line by line.
+pub fn run_repl(input: &str, options: &[&str]) -> Run {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox_tree-walk_interpreter"))
+        .args(options)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not run the interpreter");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())
+        .expect("could not write to the REPL");
+    Run::from(child.wait_with_output().expect("could not run the REPL"))
+}
+
+impl From<Output> for Run {
+    fn from(output: Output) -> Self {
+        Run {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            code: output.status.code(),
+        }
+    }
+}
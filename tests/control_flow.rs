@@ -0,0 +1,94 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn loop_bodies_start_each_iteration_with_fresh_variables() {
+    let run = run_lox(
+        "for (var i = 0; i < 3; i = i + 1) {\n  var count = 0;\n  count = count + i;\n  print count;\n}\nvar j = 0;\nwhile (j < 2) { var seen = j; j = j + 1; print seen; }",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("0\n1\n2\n0\n1\n");
+}
+
+#[test]
+fn deep_nesting_is_a_stack_overflow_error() {
+    let source = format!("print {}1{};", "(".repeat(2000), ")".repeat(2000));
+    let run = run_lox(&source, &[]);
+    run.assert_code(70);
+    run.assert_error("Stack overflow: evaluation nested deeper than 1000 levels.");
+}
+
+#[test]
+fn continue_in_a_for_loop_still_runs_the_update() {
+    let run = run_lox(
+        "for (var i = 0; i < 5; i = i + 1) {\n  if (i == 1) continue;\n  if (i == 3) break;\n  print i;\n}",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("0\n2\n");
+}
+
+#[test]
+fn for_loop_without_a_condition_runs_until_break() {
+    let run = run_lox(
+        "var n = 0;\nfor (;;) { n = n + 1; if (n == 3) break; }\nprint n;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("3\n");
+}
+
+#[test]
+fn labeled_break_and_continue_reach_the_outer_loop() {
+    let run = run_lox(
+        "outer: for (var i = 0; i < 3; i = i + 1) {\n  for (var j = 0; j < 3; j = j + 1) {\n    if (j == 1) continue outer;\n    if (i == 2) break outer;\n    print i, j;\n  }\n}\nprint \"done\";",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("0 0\n1 0\ndone\n");
+}
+
+#[test]
+fn undefined_labels_are_syntax_errors() {
+    let run = run_lox("while (true) { break nowhere; }", &[]);
+    run.assert_code(65);
+    run.assert_error("Undefined label 'nowhere'.");
+}
+
+#[test]
+fn loops_stop_at_the_iteration_limit() {
+    let source = "var i = 0;\nwhile (true) { i = i + 1; }";
+    let run = run_lox(source, &["--max-loop-iterations=100"]);
+    run.assert_code(70);
+    run.assert_error("[Line 2, Column 1] Error: Loop limit exceeded: more than 100 iterations.");
+
+    let run = run_lox(
+        "for (var i = 0; i < 100; i = i + 1) {}\nprint \"done\";",
+        &["--max-loop-iterations=100"],
+    );
+    run.assert_code(0);
+    run.assert_output("done\n");
+}
+
+#[test]
+fn break_and_continue_outside_loops_are_syntax_errors() {
+    let run = run_lox("{ break; }", &[]);
+    run.assert_code(65);
+    run.assert_error("[Line 1, Column 3] Error: Cannot use 'break' outside of a loop or switch.");
+
+    let run = run_lox("continue;", &[]);
+    run.assert_code(65);
+    run.assert_error("[Line 1, Column 1] Error: Cannot use 'continue' outside of a loop.");
+}
+
+#[test]
+fn labeled_loops_work_inside_block_expressions() {
+    let run = run_lox(
+        "var x = { var n = 0; outer: while (true) { while (true) { n = n + 1; break outer; } } n };\nprint x;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("1\n");
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn type_errors_point_at_the_operator() {
+    let run = run_lox("print 1 + nil;", &["--error-format=json"]);
+    run.assert_code(70);
+    run.assert_error("\"line\":1,\"column\":9,\"severity\":\"error\",\"phase\":\"runtime\"");
+}
+
+#[test]
+fn errors_underline_the_whole_token() {
+    let run = run_lox("var abc = 1;\nprint abcd;", &[]);
+    run.assert_code(70);
+    run.assert_error(
+        "[Line 2, Column 7] Error: Undefined Variable: abcd\n    print abcd;\n          ^~~~\n",
+    );
+}
+
+#[test]
+fn json_errors_are_one_object_per_line() {
+    let run = run_lox("print @;\nprint #;", &["--error-format=json"]);
+    run.assert_code(65);
+    assert_eq!(
+        run.stderr,
+        "{\"line\":1,\"column\":7,\"severity\":\"error\",\"phase\":\"scan\",\"message\":\"Unexpected character '@' (U+0040).\"}\n\
+         {\"line\":2,\"column\":7,\"severity\":\"error\",\"phase\":\"scan\",\"message\":\"Unexpected character '#' (U+0023).\"}\n"
+    );
+}
@@ -0,0 +1,74 @@
+use lox_tree_walk_interpreter::{
+    environment::{Environment, EnvironmentError},
+    interner::Symbol,
+    token::Literal,
+};
+
+#[test]
+fn get_at_and_assign_at_address_scopes_by_depth() {
+    let name = Symbol::intern("depth_test");
+    let mut environment = Environment::new();
+    environment.define(name, Some(Literal::Number(1.0)));
+    environment.increase_scope();
+    environment.define(name, Some(Literal::Number(2.0)));
+    environment.increase_scope();
+
+    assert_eq!(environment.get_at(1, name), Ok(Literal::Number(2.0)));
+    assert_eq!(environment.get_at(2, name), Ok(Literal::Number(1.0)));
+    assert_eq!(
+        environment.get_at(0, name),
+        Err(EnvironmentError::UndefinedVariable)
+    );
+    assert_eq!(
+        environment.get_at(3, name),
+        Err(EnvironmentError::UndefinedVariable)
+    );
+
+    environment
+        .assign_at(2, name, Literal::Number(3.0))
+        .expect("the global is defined");
+    assert_eq!(environment.get(name), Ok(Literal::Number(2.0)));
+    assert_eq!(environment.get_at(2, name), Ok(Literal::Number(3.0)));
+}
+
+#[test]
+fn dump_lists_visible_bindings_innermost_first() {
+    let mut environment = Environment::new();
+    environment.define(Symbol::intern("dump_b"), Some(Literal::Number(2.0)));
+    environment.define(Symbol::intern("dump_a"), Some(Literal::Number(1.0)));
+    environment.increase_scope();
+    environment.define(Symbol::intern("dump_b"), None);
+
+    assert_eq!(environment.dump(), "dump_b = <uninitialized>\ndump_a = 1");
+    assert_eq!(environment.bindings().count(), 1);
+}
+
+#[test]
+fn define_reports_whether_it_replaced_a_binding() {
+    let name = Symbol::intern("define_test");
+    let mut environment = Environment::new();
+    assert!(!environment.define(name, None));
+    assert!(environment.define(name, Some(Literal::Nil)));
+    environment.increase_scope();
+    assert!(!environment.define(name, None));
+}
+
+#[test]
+fn reused_scopes_start_empty() {
+    let name = Symbol::intern("pooled_test");
+    let mut environment = Environment::new();
+    environment.increase_scope();
+    environment.define(name, Some(Literal::Number(1.0)));
+    environment.reduce_scope().unwrap();
+    environment.increase_scope();
+
+    assert_eq!(
+        environment.get(name),
+        Err(EnvironmentError::UndefinedVariable)
+    );
+    environment.reduce_scope().unwrap();
+    assert_eq!(
+        environment.reduce_scope(),
+        Err(EnvironmentError::CannotReduceGlobalScope)
+    );
+}
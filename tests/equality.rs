@@ -0,0 +1,30 @@
+use lox_tree_walk_interpreter::interpreter::{values_equal, Value};
+
+#[test]
+fn values_of_different_types_are_never_equal() {
+    assert!(!values_equal(
+        &Value::Number(1.0),
+        &Value::String("1".into())
+    ));
+    assert!(!values_equal(&Value::Nil, &Value::Boolean(false)));
+    assert!(!values_equal(&Value::Number(0.0), &Value::Nil));
+}
+
+#[test]
+fn nan_is_not_equal_to_itself() {
+    assert!(!values_equal(
+        &Value::Number(f64::NAN),
+        &Value::Number(f64::NAN)
+    ));
+}
+
+#[test]
+fn values_of_one_type_compare_by_content() {
+    assert!(values_equal(&Value::Nil, &Value::Nil));
+    assert!(values_equal(&Value::Number(2.0), &Value::Number(2.0)));
+    assert!(values_equal(
+        &Value::String("a".into()),
+        &Value::String("a".into())
+    ));
+    assert!(!values_equal(&Value::Boolean(true), &Value::Boolean(false)));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::run_lox;
+
+/// A script with `count` scan errors, one unexpected character per line.
+fn script_with_errors(count: usize) -> String {
+    "@\n".repeat(count)
+}
+
+#[test]
+fn exactly_the_limit_of_errors_prints_no_note() {
+    let run = run_lox(&script_with_errors(20), &[]);
+    run.assert_code(65);
+    assert_eq!(run.stderr.matches("Error:").count(), 20);
+    assert!(!run.stderr.contains("Too many errors"));
+}
+
+#[test]
+fn errors_beyond_the_limit_are_dropped_with_a_note() {
+    let run = run_lox(&script_with_errors(25), &[]);
+    run.assert_code(65);
+    assert_eq!(run.stderr.matches("Error:").count(), 20);
+    assert_eq!(
+        run.stderr
+            .matches("Too many errors (20), stopping.")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn json_diagnostics_are_limited_too() {
+    let run = run_lox(&script_with_errors(25), &["--error-format=json"]);
+    run.assert_code(65);
+    let lines: Vec<_> = run.stderr.lines().collect();
+    assert_eq!(lines.len(), 21);
+    assert!(lines[..20]
+        .iter()
+        .all(|line| line.contains("\"severity\":\"error\"")));
+    assert!(lines[20].contains("\"severity\":\"note\""));
+    assert!(lines[20].contains("Too many errors (20), stopping."));
+}
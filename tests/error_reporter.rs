@@ -0,0 +1,19 @@
+use lox_tree_walk_interpreter::{error_reporter::tokens_to_source, scanner::scan};
+
+/// Scans `source` and rebuilds it from its tokens.
+fn round_trip(source: &str) -> String {
+    let (tokens, diagnostics) = scan(source);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    tokens_to_source(&tokens)
+}
+
+#[test]
+fn tokens_rebuild_normally_spaced_source() {
+    assert_eq!(round_trip("print   -( a+b ) ;"), "print -(a + b);");
+    assert_eq!(round_trip("var x=( 1,2 );"), "var x = (1, 2);");
+}
+
+#[test]
+fn binary_minus_keeps_its_spaces() {
+    assert_eq!(round_trip("a-b"), "a - b");
+}
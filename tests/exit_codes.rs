@@ -0,0 +1,48 @@
+mod common;
+
+use common::{run_lox, run_lox_with_env};
+
+#[test]
+fn a_successful_script_exits_with_zero() {
+    run_lox("print 1;", &[]).assert_code(0);
+}
+
+#[test]
+fn a_syntax_error_exits_with_65() {
+    let run = run_lox("print 1 +;", &[]);
+    run.assert_code(65);
+    assert!(run.stdout.is_empty(), "the program must not run");
+}
+
+#[test]
+fn a_runtime_error_exits_with_70_and_stops_the_program() {
+    let run = run_lox("print \"before\";\nprint 1 + nil;\nprint \"after\";", &[]);
+    run.assert_code(70);
+    run.assert_output("before\n");
+}
+
+#[test]
+fn a_missing_script_exits_with_66() {
+    let run = std::process::Command::new(env!("CARGO_BIN_EXE_lox_tree-walk_interpreter"))
+        .arg("no/such/script.lox")
+        .output()
+        .expect("could not run the interpreter");
+    assert_eq!(run.status.code(), Some(66));
+}
+
+#[test]
+fn an_internal_error_is_reported_at_the_last_statement_and_exits_with_101() {
+    let source = "print 1;\n  print 2;";
+    let env = [("LOX_PANIC_AFTER_RUN", "1")];
+    let run = run_lox_with_env(source, &[], &env);
+    run.assert_code(101);
+    run.assert_error("[Line 2, Column 3] Error: Internal error: LOX_PANIC_AFTER_RUN is set");
+
+    let run = run_lox_with_env(source, &["--error-format=json"], &env);
+    run.assert_code(101);
+    assert_eq!(
+        run.stderr,
+        "{\"line\":2,\"column\":3,\"severity\":\"error\",\"phase\":\"internal\",\
+         \"message\":\"Internal error: LOX_PANIC_AFTER_RUN is set\"}\n"
+    );
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn a_sequence_yields_its_last_expression() {
+    let run = run_lox(
+        "var a = (1, 2, 3);\nvar b = 0;\nvar c = (b = 5, b + 1);\nprint a, b, c;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("3 5 6\n");
+}
+
+#[test]
+fn bitwise_operators_work_on_integers() {
+    let run = run_lox("print 6 & 3, 6 | 3, 6 ^ 3, 1 << 4, 256 >> 2;", &[]);
+    run.assert_code(0);
+    run.assert_output("2 7 5 16 64\n");
+}
+
+#[test]
+fn bitwise_operators_reject_fractions_and_wide_shifts() {
+    let run = run_lox("print 1.5 & 1;", &[]);
+    run.assert_code(70);
+    run.assert_error("Operands of & must be integers");
+    let run = run_lox("print 1 << 64;", &[]);
+    run.assert_code(70);
+    run.assert_error("Shift amount 64 is out of range");
+}
+
+#[test]
+fn power_is_right_associative_and_binds_tighter_than_negation() {
+    let run = run_lox("print 2 ** 3 ** 2, -2 ** 2, 2 ** -1;", &[]);
+    run.assert_code(0);
+    run.assert_output("512 -4 0.5\n");
+}
+
+#[test]
+fn increment_and_decrement_return_the_old_or_new_value() {
+    let run = run_lox("var i = 1;\nprint i++, i, ++i, i--, --i;", &[]);
+    run.assert_code(0);
+    run.assert_output("1 2 3 3 1\n");
+}
+
+#[test]
+fn increment_needs_a_variable() {
+    let run = run_lox("3++;", &[]);
+    run.assert_code(65);
+    run.assert_error("Invalid ++ target, expected a variable.");
+}
+
+#[test]
+fn typeof_names_the_type_of_its_operand() {
+    let run = run_lox(
+        "var s = 1;\nprint typeof s, typeof \"s\", typeof nil, typeof true, typeof {}, typeof (1 + 1);",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("number string nil boolean map number\n");
+}
+
+#[test]
+fn a_block_expression_yields_its_tail() {
+    let run = run_lox(
+        "var x = { var y = 1; y + 1 };\nprint x;\nprint { 1; };",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("2\nnil\n");
+}
+
+#[test]
+fn break_cannot_leave_a_block_expression() {
+    let run = run_lox("for (;;) { var z = { break; }; }", &[]);
+    run.assert_code(65);
+    run.assert_error("Cannot use 'break' outside of a loop or switch.");
+}
+
+#[test]
+fn overflowing_arithmetic_is_an_error() {
+    let run = run_lox("print 10 ** 400;", &[]);
+    run.assert_code(70);
+    run.assert_error("Arithmetic overflow: 10 ** 400 is too large to represent");
+
+    let run = run_lox("print 1 << 63;", &[]);
+    run.assert_code(70);
+    run.assert_error("Integer overflow: 1 << 63 does not fit in 64 bits");
+}
+
+#[test]
+fn results_that_fit_are_not_overflows() {
+    let run = run_lox("print 1 << 62;\nprint 10 ** 300 * 10;", &[]);
+    run.assert_code(0);
+    run.assert_output("4611686018427387904\n1e301\n");
+}
+
+#[test]
+fn dividing_by_zero_is_an_error() {
+    let run = run_lox("print \"before\";\nprint 0 / 0;\nprint \"after\";", &[]);
+    run.assert_code(70);
+    run.assert_error("[Line 2, Column 9] Error: Division by zero.");
+    run.assert_output("before\n");
+}
+
+#[test]
+fn and_or_return_the_deciding_operand_and_short_circuit() {
+    let run = run_lox(
+        "var probe = \"untouched\";\nprint nil or \"x\";\nprint false or nil;\nprint 1 and 2;\nprint nil and (probe = \"touched\");\nprint probe;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("x\nnil\n2\nnil\nuntouched\n");
+}
+
+#[test]
+fn nil_coalescing_only_replaces_nil() {
+    let run = run_lox(
+        "var probe = 0;\nprint nil ?? \"d\";\nprint false ?? \"d\";\nprint 1 ?? (probe = 1);\nprint probe;\nprint nil ?? nil ?? 3;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("d\nfalse\n1\n0\n3\n");
+}
+
+#[test]
+fn infinity_and_nan_are_literals() {
+    let run = run_lox(
+        "print Infinity;\nprint -Infinity;\nprint NaN;\nprint NaN == NaN;\nprint Infinity + 1 == Infinity;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("Infinity\n-Infinity\nNaN\nfalse\ntrue\n");
+}
@@ -0,0 +1,73 @@
+use lox_tree_walk_interpreter::{
+    interner::{Interner, Symbol},
+    interpreter::{Interpreter, Value},
+    parser::{parse_expr, Parser},
+    scanner::scan,
+    token::{Lexeme, TokenType},
+};
+
+#[test]
+fn interning_the_same_text_gives_the_same_symbol() {
+    let mut interner = Interner::new();
+    let first = interner.intern("count");
+    let other = interner.intern("total");
+    assert_eq!(interner.intern("count"), first);
+    assert_ne!(first, other);
+    assert_eq!(interner.resolve(first), "count");
+    assert_eq!(interner.resolve(other), "total");
+}
+
+#[test]
+fn symbols_display_their_text() {
+    let symbol = Symbol::intern("answer");
+    assert_eq!(symbol, Symbol::intern("answer"));
+    assert_eq!(symbol.to_string(), "answer");
+    assert_eq!(symbol.with_str(str::len), 6);
+}
+
+#[test]
+fn identifiers_and_keywords_hold_symbols() {
+    let (tokens, diagnostics) = scan("var name = \"text\" + 12.5;");
+    assert!(diagnostics.is_empty());
+    assert_eq!(tokens[0].lexeme, Lexeme::Symbol(Symbol::intern("var")));
+    assert_eq!(tokens[1].token_type, TokenType::Identifier);
+    assert_eq!(tokens[1].lexeme, Lexeme::Symbol(Symbol::intern("name")));
+    assert_eq!(tokens[1].symbol(), Symbol::intern("name"));
+    for token in &tokens[2..] {
+        assert!(matches!(token.lexeme, Lexeme::Text(_)), "{:?}", token);
+    }
+    let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme()).collect();
+    assert_eq!(lexemes, ["var", "name", "=", "\"text\"", "+", "12.5", ";"]);
+}
+
+#[test]
+fn repeated_names_share_one_symbol_without_copying_text() {
+    let source = "counter = counter + 1;\n".repeat(10_000);
+    let (tokens, diagnostics) = scan(&source);
+    assert!(diagnostics.is_empty());
+    let counter = Symbol::intern("counter");
+    let names: Vec<_> = tokens
+        .iter()
+        .filter(|token| token.token_type == TokenType::Identifier)
+        .collect();
+    assert_eq!(names.len(), 20_000);
+    for token in names {
+        assert_eq!(token.lexeme, Lexeme::Symbol(counter));
+        assert_eq!(token.symbol(), counter);
+    }
+}
+
+#[test]
+fn names_resolve_the_same_through_symbols() {
+    let (tokens, diagnostics) =
+        scan("var total = 0; var i = 0; while (i < 1000) { total = total + i; i = i + 1; }");
+    assert!(diagnostics.is_empty());
+    let program = Parser::new(&tokens).parse_program();
+    let mut interpreter = Interpreter::new();
+    interpreter.evaluate_program(&program).unwrap();
+    let total = parse_expr("total").expect("a valid expression");
+    assert_eq!(
+        interpreter.eval_expression(&total),
+        Ok(Value::Number(499500.0))
+    );
+}
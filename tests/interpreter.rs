@@ -0,0 +1,54 @@
+use lox_tree_walk_interpreter::{
+    error_reporter::RuntimeError,
+    interpreter::{Interpreter, Value},
+    parser::{parse_expr, Parser},
+    scanner::scan,
+};
+
+/// Parses `source`, which must be a valid expression.
+fn expression(source: &str) -> lox_tree_walk_interpreter::ast::Expression {
+    parse_expr(source).expect("a valid expression")
+}
+
+/// Returns an interpreter that has run the declarations in `source`.
+fn interpreter_after(source: &str) -> Interpreter {
+    let (tokens, _) = scan(source);
+    let program = Parser::new(&tokens).parse_program();
+    let mut interpreter = Interpreter::new();
+    interpreter.evaluate_program(&program).unwrap();
+    interpreter
+}
+
+#[test]
+fn eval_expression_sees_the_current_environment() {
+    let mut interpreter = interpreter_after("var x = 2;");
+    assert_eq!(
+        interpreter.eval_expression(&expression("x * 21")),
+        Ok(Value::Number(42.0))
+    );
+}
+
+#[test]
+fn eval_expression_returns_runtime_errors() {
+    let mut interpreter = interpreter_after("var unset;");
+    assert!(matches!(
+        interpreter.eval_expression(&expression("1 + nil")),
+        Err(RuntimeError::TypeMismatch {
+            line: 1,
+            column: 3,
+            ..
+        })
+    ));
+    assert_eq!(
+        interpreter.eval_expression(&expression("1 / (2 - 2)")),
+        Err(RuntimeError::DivisionByZero { line: 1, column: 3 })
+    );
+    assert!(matches!(
+        interpreter.eval_expression(&expression("missing")),
+        Err(RuntimeError::UndefinedVariable { .. })
+    ));
+    assert_eq!(
+        interpreter.eval_expression(&expression("unset")),
+        Err(RuntimeError::Reported)
+    );
+}
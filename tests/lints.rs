@@ -0,0 +1,35 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn unused_variables_are_reported_as_warnings() {
+    let run = run_lox(
+        "var unused = 1;\n{ var local = 2; var _ignored = 3; }\nvar used = 4;\nprint used;",
+        &["--lint"],
+    );
+    run.assert_code(0);
+    run.assert_error("Warning: Unused variable: local");
+    run.assert_error("Warning: Unused variable: unused");
+    assert!(!run.stderr.contains("Unused variable: _ignored"));
+    assert!(!run.stderr.contains("Unused variable: used"));
+    run.assert_output("4\n");
+}
+
+#[test]
+fn warnings_are_only_reported_with_lint() {
+    let run = run_lox("var unused = 1;", &[]);
+    run.assert_code(0);
+    assert!(run.stderr.is_empty());
+}
+
+#[test]
+fn code_after_break_is_reported_as_unreachable() {
+    let run = run_lox(
+        "while (true) {\n  break;\n  print \"never\";\n}\nprint \"end\";",
+        &["--lint"],
+    );
+    run.assert_code(0);
+    run.assert_error("[Line 3, Column 3] Warning: Unreachable code.");
+    run.assert_output("end\n");
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn maps_can_start_with_any_literal_key() {
+    let run = run_lox(
+        "var a = {1: \"x\", \"b\": 2};\nvar b = {true: 1, nil: 2};\nvar c = {NaN: 3};\nprint a[1], a[\"b\"], b[true], b[nil], c[NaN];",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("x 2 1 2 3\n");
+}
+
+#[test]
+fn empty_braces_are_an_empty_map() {
+    let run = run_lox("var m = {};\nm[0] = \"zero\";\nprint m[0];", &[]);
+    run.assert_code(0);
+    run.assert_output("zero\n");
+}
+
+#[test]
+fn other_braces_are_block_expressions() {
+    let run = run_lox("var x = { var y = 1; y + 1 };\nprint x;", &[]);
+    run.assert_code(0);
+    run.assert_output("2\n");
+}
+
+#[test]
+fn negative_zero_and_zero_are_one_key() {
+    let run = run_lox("var m = {0: \"a\"};\nm[-0] = \"b\";\nprint m[0];", &[]);
+    run.assert_code(0);
+    run.assert_output("b\n");
+}
+
+#[test]
+fn a_map_cannot_be_a_key() {
+    let run = run_lox("var m = {};\nm[{}] = 1;", &[]);
+    run.assert_code(70);
+}
+
+#[test]
+fn maps_are_shared_between_copies() {
+    let run = run_lox(
+        "var m = {\"b\": 1};\nvar alias = m;\nalias[\"a\"] = 2;\nprint m[\"a\"];\nprint m[\"missing\"];\nprint m;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("2\nnil\n{\"a\": 2, \"b\": 1}\n");
+}
+
+#[test]
+fn braces_at_statement_position_are_always_blocks() {
+    let run = run_lox("{}\n{ print \"in block\"; }\nprint \"ok\";", &[]);
+    run.assert_code(0);
+    run.assert_output("in block\nok\n");
+
+    let run = run_lox("{ \"a\": 1 }", &[]);
+    run.assert_code(65);
+    run.assert_error("Expect ';' after expression.");
+}
+
+#[test]
+fn maps_are_equal_when_their_entries_are() {
+    let run = run_lox(
+        "print {\"a\": {\"b\": 1}} == {\"a\": {\"b\": 1}};\nprint {\"a\": 1} == {\"a\": 2};\nprint {\"a\": 1} == {\"a\": 1, \"b\": 2};\nvar m = {}; m[\"self\"] = m;\nvar n = {}; n[\"self\"] = n;\nprint m == n;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("true\nfalse\nfalse\ntrue\n");
+}
@@ -0,0 +1,27 @@
+use lox_tree_walk_interpreter::token::format_number;
+
+#[test]
+fn whole_numbers_print_without_a_decimal_point() {
+    assert_eq!(format_number(5.0), "5");
+    assert_eq!(format_number(-12.0), "-12");
+    assert_eq!(format_number(1e20), "100000000000000000000");
+}
+
+#[test]
+fn large_numbers_switch_to_exponent_notation() {
+    assert_eq!(format_number(1e21), "1e21");
+    assert_eq!(format_number(1.5e22), "1.5e22");
+}
+
+#[test]
+fn special_values_print_as_their_keywords() {
+    assert_eq!(format_number(f64::INFINITY), "Infinity");
+    assert_eq!(format_number(f64::NEG_INFINITY), "-Infinity");
+    assert_eq!(format_number(f64::NAN), "NaN");
+}
+
+#[test]
+fn fractions_print_in_full() {
+    assert_eq!(format_number(1.5), "1.5");
+    assert_eq!(format_number(0.1 + 0.2), "0.30000000000000004");
+}
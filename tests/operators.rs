@@ -0,0 +1,20 @@
+use lox_tree_walk_interpreter::token::{Operator, TokenType, UnknownOperator};
+
+#[test]
+fn operators_parse_back_from_their_display() {
+    for text in ["-", "+", "**", "!=", "<=", "<<", ">>", "??", "++"] {
+        let operator: Operator = text.parse().unwrap();
+        assert_eq!(operator.to_string(), text);
+    }
+    assert_eq!(
+        "=>".parse::<Operator>(),
+        Err(UnknownOperator("=>".to_string()))
+    );
+}
+
+#[test]
+fn keywords_map_to_their_token_types() {
+    assert_eq!(TokenType::keyword("while"), Some(TokenType::While));
+    assert_eq!(TokenType::keyword("NaN"), Some(TokenType::NaN));
+    assert_eq!(TokenType::keyword("whilst"), None);
+}
@@ -0,0 +1,133 @@
+use lox_tree_walk_interpreter::{
+    ast::ExprKind,
+    parser::{parse_expr, Parser, ReplItem},
+    pretty_printer::PrettyPrinter,
+    rpn_printer::RpnPrinter,
+    scanner::scan,
+};
+
+#[test]
+fn parse_expr_parses_a_whole_expression() {
+    let expression = parse_expr("1 + 2 * 3").expect("a valid expression");
+    assert_eq!(
+        PrettyPrinter::new().print_expression(&expression),
+        "(+ 1 (* 2 3))"
+    );
+}
+
+#[test]
+fn parse_expr_rejects_leftover_and_missing_input() {
+    assert!(parse_expr("1 + 2 3").is_err());
+    assert!(parse_expr("").is_err());
+    let diagnostics = parse_expr("1 @ 2").unwrap_err();
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn only_variables_and_map_entries_are_assignable() {
+    let assignable = |source| parse_expr(source).unwrap().is_assignable_target();
+    assert!(assignable("x"));
+    assert!(assignable("m[\"key\"]"));
+    assert!(!assignable("(x)"));
+    assert!(!assignable("1 + x"));
+}
+
+#[test]
+fn declarations_and_expressions_start_at_their_first_token() {
+    let (tokens, _) = scan("var a = 1;\n  print (a) or 2;\n  a = 3;");
+    let program = Parser::new(&tokens).parse_program();
+    let positions: Vec<_> = program
+        .iter()
+        .map(|decl| (decl.line, decl.column))
+        .collect();
+    assert_eq!(positions, [(1, 1), (2, 3), (3, 3)]);
+
+    let expression = parse_expr("(1) or 2").unwrap();
+    assert_eq!((expression.line, expression.column), (1, 1));
+}
+
+#[test]
+fn repl_lines_are_classified() {
+    let classify = |source| {
+        let (tokens, _) = scan(source);
+        match Parser::new(&tokens).parse_repl_line() {
+            Ok(ReplItem::Expr(_)) => "expression",
+            Ok(ReplItem::Stmt(_)) => "statement",
+            Ok(ReplItem::Decl(_)) => "declaration",
+            Err(_) => "error",
+        }
+    };
+    assert_eq!(classify("1 + 1"), "expression");
+    assert_eq!(classify("print 1;"), "statement");
+    assert_eq!(classify("var x = 1;"), "declaration");
+}
+
+#[test]
+fn binary_nodes_record_the_operator_position() {
+    let expression = parse_expr("1 +\n  2 * 3").unwrap();
+    let ExprKind::Binary {
+        op_line,
+        op_column,
+        right,
+        ..
+    } = expression.kind
+    else {
+        panic!("expected a binary expression, got {:?}", expression.kind);
+    };
+    assert_eq!((op_line, op_column), (1, 3));
+    let ExprKind::Binary {
+        op_line, op_column, ..
+    } = right.kind
+    else {
+        panic!("expected a binary expression, got {:?}", right.kind);
+    };
+    assert_eq!((op_line, op_column), (2, 5));
+}
+
+#[test]
+fn pretty_printer_can_show_positions() {
+    let expression = parse_expr("x +\n  1").unwrap();
+    assert_eq!(
+        PrettyPrinter::new()
+            .with_positions(true)
+            .print_expression(&expression),
+        "(+ @1:1 x@1:1 1@2:3)"
+    );
+}
+
+#[test]
+fn positions_do_not_depend_on_the_printed_text() {
+    let with_positions = |source| {
+        PrettyPrinter::new()
+            .with_positions(true)
+            .print_expression(&parse_expr(source).unwrap())
+    };
+    assert_eq!(with_positions("\"a b\""), "\"a b\"@1:1");
+    assert_eq!(with_positions("\"ab\""), "\"ab\"@1:1");
+    assert_eq!(
+        with_positions("typeof \"a b\""),
+        "(typeof @1:1 \"a b\"@1:8)"
+    );
+    assert_eq!(with_positions("{\"k v\": 1}"), "@1:1 {\"k v\"@1:2: 1@1:9}");
+    assert_eq!(
+        with_positions("m[\"k v\"] = -1"),
+        "@1:1 (index m@1:1 \"k v\"@1:3) = (- @1:12 1@1:13)"
+    );
+}
+
+#[test]
+fn struct_eq_ignores_positions_and_groupings() {
+    let same = |a, b| parse_expr(a).unwrap().struct_eq(&parse_expr(b).unwrap());
+    assert!(same("1 + 2 * 3", "1 +\n   (2 * 3)"));
+    assert!(same("NaN", "NaN"));
+    assert!(!same("1 + 2", "2 + 1"));
+    assert!(!same("(1 + 2) * 3", "1 + 2 * 3"));
+}
+
+#[test]
+fn rpn_printer_writes_postfix() {
+    let rpn = |source| RpnPrinter::new().print_expression(&parse_expr(source).unwrap());
+    assert_eq!(rpn("(1 + 2) * 3"), "1 2 + 3 *");
+    assert_eq!(rpn("-x - 4"), "x u- 4 -");
+    assert_eq!(rpn("{\"a\": 1}"), "\"a\" 1 {1}");
+}
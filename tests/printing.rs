@@ -0,0 +1,43 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn print_shows_strings_without_quotes() {
+    let run = run_lox("print \"hello\";\nprint \"a\" + \"b\";", &[]);
+    run.assert_code(0);
+    run.assert_output("hello\nab\n");
+}
+
+#[test]
+fn print_separates_several_values_with_spaces() {
+    let run = run_lox("var a = 1;\nprint a, \"two\", a + 2;", &[]);
+    run.assert_code(0);
+    run.assert_output("1 two 3\n");
+}
+
+#[test]
+fn else_if_chains_are_printed_flat() {
+    let run = run_lox(
+        "var x = 2;\nif (x == 1) { print \"one\"; } else if (x == 2) { print \"two\"; } else { print \"many\"; }",
+        &[],
+    );
+    run.assert_code(0);
+    assert!(
+        run.stdout.contains(
+            "if ((== x 1)) {\n  print \"one\";\n} else if ((== x 2)) {\n  print \"two\";\n} else {\n  print \"many\";\n}\n"
+        ),
+        "{}",
+        run.stdout
+    );
+    run.assert_output("two\n");
+}
+
+#[test]
+fn precision_rounds_printed_numbers() {
+    let source = "print 0.1 + 0.2;\nprint 2 / 3;\nprint 5;";
+    run_lox(source, &[]).assert_output("0.30000000000000004\n0.6666666666666666\n5\n");
+    let run = run_lox(source, &["--precision=3"]);
+    run.assert_code(0);
+    run.assert_output("0.3\n0.667\n5\n");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::run_repl;
+
+#[test]
+fn ast_command_prints_the_parsed_line() {
+    let run = run_repl(".ast print 1 + 2 * 3;\n", &[]);
+    run.assert_code(0);
+    assert!(
+        run.stdout.contains("print (+ 1 (* 2 3));"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn tokens_command_lists_the_scanned_tokens() {
+    let run = run_repl(".tokens 1 + 2\n", &[]);
+    assert!(run.stdout.contains("Operator(Plus) +"), "{}", run.stdout);
+}
+
+#[test]
+fn clear_command_forgets_variables() {
+    let run = run_repl("var a = 1;\n.clear\nprint a;\n", &[]);
+    run.assert_error("Undefined Variable: a");
+}
+
+#[test]
+fn help_command_lists_the_commands() {
+    let run = run_repl(".help\n", &[]);
+    for command in [".help", ".tokens", ".ast", ".vars", ".clear"] {
+        assert!(run.stdout.contains(command), "{}", run.stdout);
+    }
+}
+
+#[test]
+fn echoed_strings_keep_their_quotes() {
+    let run = run_repl("\"1\";\n1;\nprint \"1\";\n", &[]);
+    run.assert_code(0);
+    assert!(run.stdout.contains("\"1\"\n"), "{}", run.stdout);
+    let output: Vec<_> = run
+        .stdout
+        .lines()
+        .filter(|line| !line.starts_with('>'))
+        .collect();
+    assert_eq!(output, ["\"1\"", "1", "1"]);
+}
+
+#[test]
+fn unfinished_input_continues_under_the_continuation_prompt() {
+    let run = run_repl("print\n1 + 2;\n", &[]);
+    run.assert_code(0);
+    assert_eq!(run.stdout, "> . print (+ 1 2);\n3\n> ");
+
+    let run = run_repl(
+        "print\n1 + 2;\n",
+        &["--prompt=lox> ", "--continuation-prompt=... "],
+    );
+    assert_eq!(run.stdout, "lox> ... print (+ 1 2);\n3\nlox> ");
+}
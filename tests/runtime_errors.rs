@@ -0,0 +1,28 @@
+use lox_tree_walk_interpreter::{error_reporter::RuntimeError, interner::Symbol};
+
+#[test]
+fn runtime_errors_carry_their_position_and_span() {
+    let error = RuntimeError::UndefinedVariable {
+        name: Symbol::intern("total"),
+        line: 3,
+        column: 7,
+    };
+    assert_eq!(error.position(), Some((3, 7)));
+    assert_eq!(error.length(), 5);
+    assert_eq!(error.to_string(), "Undefined Variable: total");
+
+    let error = RuntimeError::TypeMismatch {
+        line: 1,
+        column: 9,
+        length: 2,
+        message: "Cannot compare".to_string(),
+    };
+    assert_eq!(error.position(), Some((1, 9)));
+    assert_eq!(error.length(), 2);
+    assert_eq!(error.to_string(), "Cannot compare");
+}
+
+#[test]
+fn reported_errors_have_no_position() {
+    assert_eq!(RuntimeError::Reported.position(), None);
+}
@@ -0,0 +1,231 @@
+use std::rc::Rc;
+
+use lox_tree_walk_interpreter::{
+    error_reporter::ErrorReporter,
+    scanner::{parse_number, scan, Scanner},
+    token::{Literal, Operator, TokenType, KEYWORDS},
+};
+
+/// Scans `source`, which must be free of lexical errors.
+fn tokens(source: &str) -> Vec<lox_tree_walk_interpreter::token::Token> {
+    let (tokens, diagnostics) = scan(source);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    tokens
+}
+
+/// Scans `source`, which must be free of lexical errors, into token types.
+fn token_types(source: &str) -> Vec<TokenType> {
+    tokens(source)
+        .into_iter()
+        .map(|token| token.token_type)
+        .collect()
+}
+
+#[test]
+fn tokens_are_positioned_at_their_first_character() {
+    let positions: Vec<_> = tokens("var name = \"ü\" >= 12.5;\n  print name;")
+        .iter()
+        .map(|token| (token.lexeme().to_string(), token.line, token.column))
+        .collect();
+    let expected = [
+        ("var", 1, 1),
+        ("name", 1, 5),
+        ("=", 1, 10),
+        ("\"ü\"", 1, 12),
+        (">=", 1, 16),
+        ("12.5", 1, 19),
+        (";", 1, 23),
+        ("print", 2, 3),
+        ("name", 2, 9),
+        (";", 2, 13),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(lexeme, line, column)| (lexeme.to_string(), *line, *column))
+        .collect();
+    assert_eq!(positions, expected);
+}
+
+#[test]
+fn token_lengths_count_characters() {
+    let scanned = tokens("\"ü\"");
+    assert_eq!(scanned[0].token_type, TokenType::String);
+    assert_eq!(scanned[0].length, 3);
+}
+
+#[test]
+fn scan_returns_lexical_errors_alongside_the_tokens() {
+    let (tokens, diagnostics) = scan("var x = 1 @ 2;");
+    let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme()).collect();
+    assert_eq!(&lexemes[..6], ["var", "x", "=", "1", "2", ";"]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 11));
+}
+
+#[test]
+fn lexeme_and_token_type_read_a_token() {
+    let scanned = tokens("while");
+    assert_eq!(scanned[0].lexeme(), "while");
+    assert_eq!(scanned[0].token_type(), &TokenType::While);
+}
+
+#[test]
+fn custom_keyword_tables_replace_the_standard_one() {
+    let mut keywords = KEYWORDS.clone();
+    keywords.remove("fun");
+    keywords.insert("function", TokenType::Fun);
+    let types: Vec<_> = Scanner::with_keywords("function fun", keywords)
+        .scan_tokens()
+        .into_iter()
+        .map(|token| token.token_type)
+        .collect();
+    assert_eq!(&types[..2], [TokenType::Fun, TokenType::Identifier]);
+}
+
+#[test]
+fn unexpected_characters_are_named_by_code_point() {
+    let (_, diagnostics) = scan("print \u{7};");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Unexpected character '\\u{7}' (U+0007)."
+    );
+    assert_eq!((diagnostics[0].column, diagnostics[0].length), (7, Some(1)));
+}
+
+#[test]
+fn unicode_whitespace_separates_tokens() {
+    let lexemes: Vec<_> = tokens("print\u{a0}1;\u{b}")
+        .iter()
+        .map(|token| token.lexeme().to_string())
+        .collect();
+    assert_eq!(&lexemes[..3], ["print", "1", ";"]);
+}
+
+#[test]
+fn scanner_yields_tokens_one_at_a_time() {
+    let mut scanner = Scanner::new("print 1;");
+    assert_eq!(
+        scanner.next().map(|token| token.token_type),
+        Some(TokenType::Print)
+    );
+    assert_eq!(
+        scanner.next().map(|token| token.token_type),
+        Some(TokenType::Number)
+    );
+    let rest: Vec<_> = scanner.map(|token| token.token_type).collect();
+    assert_eq!(rest.first(), Some(&TokenType::Semicolon));
+}
+
+#[test]
+fn a_leading_bom_and_shebang_are_skipped() {
+    let scanned = tokens("\u{feff}#!/usr/bin/env lox\nprint 1;");
+    assert_eq!(scanned[0].token_type, TokenType::Print);
+    assert_eq!((scanned[0].line, scanned[0].column), (2, 1));
+}
+
+#[test]
+fn unterminated_strings_end_at_the_end_of_their_line() {
+    let (tokens, diagnostics) = scan("print \"abc;\nprint \"ok\";");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Unterminated string.");
+    assert_eq!(diagnostics[0].line, 1);
+    let second_line: Vec<_> = tokens
+        .iter()
+        .filter(|token| token.line == 2)
+        .map(|token| token.lexeme())
+        .collect();
+    assert_eq!(second_line, ["print", "\"ok\"", ";"]);
+}
+
+#[test]
+fn number_lexemes_become_number_literals() {
+    let digits = "9".repeat(400);
+    let scanned = tokens(&format!("12.5 007 {}", digits));
+    let values: Vec<_> = scanned
+        .iter()
+        .take(3)
+        .map(|token| token.literal.clone())
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Some(Literal::Number(12.5)),
+            Some(Literal::Number(7.0)),
+            Some(Literal::Number(f64::INFINITY)),
+        ]
+    );
+}
+
+#[test]
+fn malformed_number_lexemes_are_reported() {
+    let mut error_reporter = ErrorReporter::collecting();
+    assert_eq!(parse_number("1.2.3", 4, 9, &mut error_reporter), 0.0);
+    let diagnostics = error_reporter.take_diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Invalid number '1.2.3'.");
+    assert_eq!(
+        (
+            diagnostics[0].line,
+            diagnostics[0].column,
+            diagnostics[0].length
+        ),
+        (4, 9, Some(5))
+    );
+
+    assert_eq!(parse_number("12.5", 1, 1, &mut error_reporter), 12.5);
+    assert!(error_reporter.take_diagnostics().is_empty());
+}
+
+#[test]
+fn question_marks_scan_alone_or_as_nil_coalescing() {
+    let types: Vec<_> = tokens("? ?? :")
+        .iter()
+        .take(3)
+        .map(|token| token.token_type.clone())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            TokenType::Question,
+            TokenType::Operator(Operator::QuestionQuestion),
+            TokenType::Colon,
+        ]
+    );
+}
+
+#[test]
+fn identical_string_literals_share_their_text() {
+    let scanned = tokens("\"same\" \"same\" \"other\"");
+    let text = |index: usize| match &scanned[index].literal {
+        Some(Literal::String(text)) => text.clone(),
+        other => panic!("expected a string literal, got {:?}", other),
+    };
+    assert!(Rc::ptr_eq(&text(0), &text(1)));
+    assert!(!Rc::ptr_eq(&text(0), &text(2)));
+}
+
+#[test]
+fn greater_than_signs_scan_as_shift_comparison_or_two_greaters() {
+    let op = TokenType::Operator;
+    assert_eq!(token_types(">>"), [op(Operator::Shr)]);
+    assert_eq!(token_types(">="), [op(Operator::GreaterEqual)]);
+    assert_eq!(token_types(">"), [op(Operator::Greater)]);
+    assert_eq!(
+        token_types("> >"),
+        [op(Operator::Greater), op(Operator::Greater)]
+    );
+}
+
+#[test]
+fn brackets_scan_as_single_character_tokens() {
+    assert_eq!(
+        token_types("[]"),
+        [TokenType::LeftBracket, TokenType::RightBracket]
+    );
+    let brackets = tokens("m[ 1 ]");
+    assert_eq!(brackets[1].token_type, TokenType::LeftBracket);
+    assert_eq!((brackets[1].column, brackets[1].length), (2, 1));
+    assert_eq!(brackets[3].token_type, TokenType::RightBracket);
+    assert_eq!((brackets[3].column, brackets[3].length), (6, 1));
+}
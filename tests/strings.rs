@@ -0,0 +1,37 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn repeats_a_string() {
+    let run = run_lox("print \"ab\" * 3, 2 * \"c\", \"x\" * 0;", &[]);
+    run.assert_code(0);
+    run.assert_output("ababab cc \n");
+}
+
+#[test]
+fn rejects_a_repetition_too_long_to_allocate() {
+    let run = run_lox("print \"ab\" * 100000000000000;", &[]);
+    run.assert_code(70);
+    run.assert_error("String repetition result is longer than");
+}
+
+#[test]
+fn folding_a_huge_repetition_leaves_it_for_runtime() {
+    let run = run_lox(
+        "if (false) { print \"ab\" * 100000000000000; }\nprint \"done\";",
+        &["--fold"],
+    );
+    run.assert_code(0);
+    run.assert_output("done\n");
+}
+
+#[test]
+fn built_strings_equal_literals_with_the_same_text() {
+    let run = run_lox(
+        "print \"ab\" == \"a\" + \"b\";\nprint \"ab\" == \"ab\";",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("true\ntrue\n");
+}
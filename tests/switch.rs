@@ -0,0 +1,84 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn cases_do_not_fall_through_by_default() {
+    let run = run_lox(
+        "switch (1) { case 1: print \"one\"; case 2: print \"two\"; default: print \"other\"; }",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("one\n");
+}
+
+#[test]
+fn fallthrough_chains_cases_into_the_default() {
+    let run = run_lox(
+        "switch (1) {\n  case 1: print \"one\"; fallthrough;\n  case 2: print \"two\"; fallthrough;\n  default: print \"other\";\n}",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("one\ntwo\nother\n");
+}
+
+#[test]
+fn break_leaves_a_top_level_switch() {
+    let run = run_lox(
+        "switch (1) { case 1: print \"one\"; break; print \"skipped\"; }\nprint \"after\";",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("one\nafter\n");
+}
+
+#[test]
+fn break_in_a_switch_does_not_leave_the_loop() {
+    let run = run_lox(
+        "for (var i = 0; i < 3; i = i + 1) { switch (i) { case 1: break; default: print i; } }",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("0\n2\n");
+}
+
+#[test]
+fn continue_in_a_switch_outside_a_loop_is_an_error() {
+    let run = run_lox("switch (1) { case 1: continue; }", &[]);
+    run.assert_code(65);
+    run.assert_error("Cannot use 'continue' outside of a loop.");
+}
+
+#[test]
+fn fallthrough_must_end_a_case() {
+    let run = run_lox(
+        "switch (1) { case 1: fallthrough; print 1; case 2: print 2; }",
+        &[],
+    );
+    run.assert_code(65);
+    run.assert_error("'fallthrough' must be the last statement of a case.");
+}
+
+#[test]
+fn the_default_case_cannot_fall_through() {
+    let run = run_lox("switch (1) { default: fallthrough; }", &[]);
+    run.assert_code(65);
+    run.assert_error("Cannot fall through out of the default case.");
+}
+
+#[test]
+fn switch_runs_the_first_equal_case_or_the_default() {
+    let run = run_lox(
+        "for (var i = 0; i < 4; i = i + 1) {\n  switch (i) {\n    case 0: print \"zero\";\n    case 1: var s = \"one\"; print s;\n    case 1 + 1: print \"two\";\n    default: print \"other\";\n  }\n}\nswitch (\"x\") { case \"y\": print \"no\"; }",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("zero\none\ntwo\nother\n");
+}
+
+#[test]
+fn a_switch_has_at_most_one_default() {
+    let run = run_lox("switch (1) { default: print 1; default: print 2; }", &[]);
+    run.assert_code(65);
+    run.assert_error("A switch statement can only have one default case.");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::run_lox;
+
+#[test]
+fn redeclaring_in_a_local_scope_is_a_syntax_error() {
+    let run = run_lox("{ var a = 1; var a = 2; print a; } print \"after\";", &[]);
+    run.assert_code(65);
+    run.assert_error("Variable 'a' is already declared in this scope.");
+    assert!(!run.stdout.contains("after"), "the program must not run");
+}
+
+#[test]
+fn redeclaring_in_one_var_statement_is_a_syntax_error() {
+    let run = run_lox("for (var i = 0, i = 1; i < 1; i = i + 1) {}", &[]);
+    run.assert_code(65);
+    run.assert_error("Variable 'i' is already declared in this scope.");
+}
+
+#[test]
+fn globals_may_be_redeclared_and_locals_shadowed() {
+    let run = run_lox(
+        "var a = 1;\nvar a = 2;\n{ var a = 3; { var a = 4; print a; } print a; }\nfor (var i = 0; i < 1; i = i + 1) { var i = 5; print i; }\nprint a;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("4\n3\n5\n2\n");
+}
+
+#[test]
+fn reading_an_uninitialized_variable_continues_with_nil_by_default() {
+    let run = run_lox("var x;\nprint x;\nprint \"after\";", &[]);
+    run.assert_error("Uninitialized Variable: x");
+    run.assert_output("nil\nafter\n");
+}
+
+#[test]
+fn reading_an_uninitialized_variable_aborts_in_strict_mode() {
+    let run = run_lox("var x;\nprint x;\nprint \"after\";", &["--strict"]);
+    run.assert_code(70);
+    run.assert_error("Uninitialized Variable: x");
+    assert!(!run.stdout.ends_with("after\n"));
+}
+
+#[test]
+fn global_assignment_skips_local_shadows() {
+    let run = run_lox(
+        "var x = 1;\n{ var x = 2; global x = 3; print x; }\nprint x;",
+        &[],
+    );
+    run.assert_code(0);
+    run.assert_output("2\n3\n");
+}
+
+#[test]
+fn global_assignment_needs_a_declared_global() {
+    let run = run_lox("{ global nope = 1; }", &[]);
+    run.assert_code(70);
+    run.assert_error("Undefined Variable: nope");
+}
+
+#[test]
+fn one_var_statement_declares_several_variables() {
+    let run = run_lox("var a = 1, b, c = a + 1;\nb = 5;\nprint a, b, c;", &[]);
+    run.assert_code(0);
+    run.assert_output("1 5 2\n");
+}
+
+#[test]
+fn constants_cannot_be_reassigned() {
+    let run = run_lox("const x = 1;\nprint x;\nx = 2;", &[]);
+    run.assert_code(70);
+    run.assert_error("[Line 3, Column 1] Error: Cannot assign to constant 'x'.");
+    run.assert_output("1\n");
+
+    let run = run_lox("const x = 1;\nx++;", &[]);
+    run.assert_code(70);
+    run.assert_error("Cannot assign to constant 'x'.");
+}
+
+#[test]
+fn constants_need_an_initializer() {
+    let run = run_lox("const x;", &[]);
+    run.assert_code(65);
+    run.assert_error("Expect '=' after constant name.");
+}
+
+#[test]
+fn invalid_assignment_targets_are_reported_where_they_start() {
+    let run = run_lox("var a = 1;\nprint (a) = 5;\n-a = 4;", &["--check"]);
+    run.assert_code(65);
+    assert_eq!(
+        run.stderr,
+        "[Line 2, Column 7] Error: Invalid assignment target.\n    print (a) = 5;\n          ^\n\
+         [Line 3, Column 1] Error: Invalid assignment target.\n    -a = 4;\n    ^\n"
+    );
+}
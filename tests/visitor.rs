@@ -0,0 +1,76 @@
+use lox_tree_walk_interpreter::{
+    ast::{walk_expr, walk_program, ExprKind, Expression, Program, Visitor},
+    parser::Parser,
+    pretty_printer::PrettyPrinter,
+    scanner::scan,
+};
+
+/// Collects the names of the variables read, in visiting order.
+#[derive(Default)]
+struct VariableReads(Vec<String>);
+
+impl Visitor for VariableReads {
+    fn visit_expr(&mut self, expression: &Expression) {
+        if let ExprKind::Var { identifier } = &expression.kind {
+            self.0.push(identifier.to_string());
+        }
+        walk_expr(self, expression);
+    }
+}
+
+/// Counts the binary expressions visited.
+#[derive(Default)]
+struct BinaryCount(usize);
+
+impl Visitor for BinaryCount {
+    fn visit_expr(&mut self, expression: &Expression) {
+        if let ExprKind::Binary { .. } = &expression.kind {
+            self.0 += 1;
+        }
+        walk_expr(self, expression);
+    }
+}
+
+/// Parses `source`, which must be a valid program.
+fn parse(source: &str) -> Program {
+    let (tokens, diagnostics) = scan(source);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    Parser::new(&tokens).parse_program()
+}
+
+/// Returns the names of the variables `source` reads.
+fn variable_reads(source: &str) -> Vec<String> {
+    let program = parse(source);
+    let mut reads = VariableReads::default();
+    walk_program(&mut reads, &program);
+    reads.0
+}
+
+#[test]
+fn walking_reaches_nested_expressions_in_evaluation_order() {
+    let reads = variable_reads(
+        "var a = 1; var b = 2;\nif (a) { while (b < a) print a + b; }\nprint { var c = b; c };",
+    );
+    assert_eq!(reads, ["a", "b", "a", "a", "b", "b", "c"]);
+}
+
+#[test]
+fn counting_binary_expressions() {
+    let program = parse(
+        "var a = 1 + 2 * 3;\nif (a > 1 and a < 10) print -a, a / 2;\nfor (var i = 0; i < 3; i = i + 1) {}\nprint a or 1;",
+    );
+    let mut count = BinaryCount::default();
+    walk_program(&mut count, &program);
+    assert_eq!(count.0, 7);
+}
+
+#[test]
+fn visitors_can_produce_a_value_per_node() {
+    let program = parse("var a = 1 + 2;\nwhile (a < 5) a = a + 1;");
+    let mut printer = PrettyPrinter::new();
+    let printed: Vec<_> = program
+        .iter()
+        .map(|decl| printer.visit_decl(decl))
+        .collect();
+    assert_eq!(printed, ["var a = (+ 1 2);", "while((< a 5)) a = (+ a 1);"]);
+}